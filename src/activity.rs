@@ -0,0 +1,182 @@
+use chrono::{DateTime, Duration, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::recurrence::Recurrence;
+use crate::todo::{Priority, Todo, TodoList};
+
+/// Consecutive description edits closer together than this are logged as one.
+const COALESCE_WINDOW: Duration = Duration::minutes(1);
+
+/// One entry of a todo's history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityEvent {
+    pub at: DateTime<Local>,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Change {
+    Created,
+    Renamed { from: String, to: String },
+    DescriptionChanged,
+    Completed,
+    Reopened,
+    DueChanged {
+        from: Option<DateTime<Local>>,
+        to: Option<DateTime<Local>>,
+    },
+    PriorityChanged { from: Priority, to: Priority },
+    TagAdded { tag: String },
+    TagRemoved { tag: String },
+    SubtaskAdded { title: String },
+    SubtaskRemoved { title: String },
+    SubtaskCompleted { title: String },
+    SubtaskReopened { title: String },
+    RecurrenceChanged { to: Option<Recurrence> },
+    /// List names as they were at the time of the move
+    Moved { from: String, to: String },
+    Trashed,
+    Restored,
+    Archived,
+    Unarchived,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let date = |due: &Option<DateTime<Local>>| match due {
+            Some(due) => due.format("%d %b %Y").to_string(),
+            None => String::from("none"),
+        };
+
+        match self {
+            Change::Created => f.write_str("Created"),
+            Change::Renamed { from, to } => write!(f, "Renamed from \"{}\" to \"{}\"", from, to),
+            Change::DescriptionChanged => f.write_str("Description edited"),
+            Change::Completed => f.write_str("Completed"),
+            Change::Reopened => f.write_str("Reopened"),
+            Change::DueChanged { from, to } => {
+                write!(f, "Due date changed from {} to {}", date(from), date(to))
+            }
+            Change::PriorityChanged { from, to } => {
+                write!(f, "Priority changed from {} to {}", from, to)
+            }
+            Change::TagAdded { tag } => write!(f, "Tagged #{}", tag),
+            Change::TagRemoved { tag } => write!(f, "Removed tag #{}", tag),
+            Change::SubtaskAdded { title } => write!(f, "Added subtask \"{}\"", title),
+            Change::SubtaskRemoved { title } => write!(f, "Removed subtask \"{}\"", title),
+            Change::SubtaskCompleted { title } => write!(f, "Checked off \"{}\"", title),
+            Change::SubtaskReopened { title } => write!(f, "Unchecked \"{}\"", title),
+            Change::RecurrenceChanged { to: Some(recurrence) } => {
+                write!(f, "Repeats: {}", recurrence)
+            }
+            Change::RecurrenceChanged { to: None } => f.write_str("Stopped repeating"),
+            Change::Moved { from, to } => write!(f, "Moved from {} to {}", from, to),
+            Change::Trashed => f.write_str("Moved to the trash"),
+            Change::Restored => f.write_str("Restored from the trash"),
+            Change::Archived => f.write_str("Archived"),
+            Change::Unarchived => f.write_str("Restored from the archive"),
+        }
+    }
+}
+
+/// The changes that turn `before` into `after`, `before` being `None` for a
+/// todo that did not exist yet.
+pub fn diff(before: Option<&Todo>, after: &Todo, lists: &BTreeMap<usize, TodoList>) -> Vec<Change> {
+    let Some(before) = before else {
+        return vec![Change::Created];
+    };
+    let mut changes = Vec::new();
+
+    if before.title != after.title {
+        changes.push(Change::Renamed {
+            from: before.title.clone(),
+            to: after.title.clone(),
+        });
+    }
+    if before.description != after.description {
+        changes.push(Change::DescriptionChanged);
+    }
+    if before.completed != after.completed {
+        changes.push(if after.completed { Change::Completed } else { Change::Reopened });
+    }
+    if before.due != after.due {
+        changes.push(Change::DueChanged {
+            from: before.due,
+            to: after.due,
+        });
+    }
+    if before.priority != after.priority {
+        changes.push(Change::PriorityChanged {
+            from: before.priority,
+            to: after.priority,
+        });
+    }
+    for tag in after.tags.difference(&before.tags) {
+        changes.push(Change::TagAdded { tag: tag.clone() });
+    }
+    for tag in before.tags.difference(&after.tags) {
+        changes.push(Change::TagRemoved { tag: tag.clone() });
+    }
+
+    for subtask in &after.subtasks {
+        let title = subtask.title.clone();
+        match before.subtasks.iter().find(|old| old.id == subtask.id) {
+            None => changes.push(Change::SubtaskAdded { title }),
+            Some(old) if !old.completed && subtask.completed => {
+                changes.push(Change::SubtaskCompleted { title })
+            }
+            Some(old) if old.completed && !subtask.completed => {
+                changes.push(Change::SubtaskReopened { title })
+            }
+            Some(_) => {}
+        }
+    }
+    for subtask in &before.subtasks {
+        if !after.subtasks.iter().any(|new| new.id == subtask.id) {
+            changes.push(Change::SubtaskRemoved {
+                title: subtask.title.clone(),
+            });
+        }
+    }
+
+    // Completing a recurring todo hands its recurrence to the next instance
+    let handed_on = after.completed && !before.completed && after.recurrence.is_none();
+    if before.recurrence != after.recurrence && !handed_on {
+        changes.push(Change::RecurrenceChanged {
+            to: after.recurrence.clone(),
+        });
+    }
+    if before.list_id != after.list_id {
+        let name = |id: usize| {
+            lists
+                .get(&id)
+                .map_or_else(|| String::from("a deleted list"), |list| list.name.clone())
+        };
+        changes.push(Change::Moved {
+            from: name(before.list_id),
+            to: name(after.list_id),
+        });
+    }
+    if before.deleted_at != after.deleted_at {
+        changes.push(if after.is_trashed() { Change::Trashed } else { Change::Restored });
+    }
+
+    changes
+}
+
+/// Appends `change` to the history of `todo`, merging a description edit
+/// into the one just before it.
+pub fn record(todo: &mut Todo, change: Change, at: DateTime<Local>) {
+    if change == Change::DescriptionChanged {
+        if let Some(last) = todo.activity.last_mut() {
+            if last.change == Change::DescriptionChanged && at - last.at < COALESCE_WINDOW {
+                last.at = at;
+                return;
+            }
+        }
+    }
+    todo.activity.push(ActivityEvent { at, change });
+}
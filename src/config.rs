@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::storage::{self, StorageKind};
+
+/// Environment variable naming the data file to open, overridden by `--data`.
+pub const DATA_PATH_VAR: &str = "TODO_GUI_DATA";
+
+/// How many workspaces the "Recent workspaces" menu remembers.
+const MAX_RECENT_WORKSPACES: usize = 8;
+
+/// Installation-wide settings read from `config.json` in the config directory.
+/// Unlike `todo::Settings` these are needed before the todos can be loaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub storage: StorageKind,
+    /// Data file to open instead of the default one for `storage`, its
+    /// backend chosen by its extension. Set when a workspace is opened.
+    #[serde(default)]
+    pub data_path: Option<PathBuf>,
+    /// Data files opened before, most recent first
+    #[serde(default)]
+    pub recent_workspaces: Vec<PathBuf>,
+    /// Minutes without input after which an encrypted workspace is locked,
+    /// 0 keeps it unlocked
+    #[serde(default = "default_auto_lock_minutes")]
+    pub auto_lock_minutes: u32,
+}
+
+fn default_auto_lock_minutes() -> u32 {
+    10
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            storage: StorageKind::default(),
+            data_path: None,
+            recent_workspaces: Vec::new(),
+            auto_lock_minutes: default_auto_lock_minutes(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> PathBuf {
+        let mut path = dirs::config_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("todo_gui");
+        path.push("config.json");
+        path
+    }
+
+    /// Reads the config file, falling back to the defaults if it is missing or invalid.
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(data) = fs::read_to_string(&path) else {
+            return Self::default();
+        };
+
+        serde_json::from_str(&data).unwrap_or_else(|e| {
+            log::warn!("Ignoring invalid config file {}: {}", path.display(), e);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        storage::json::write_atomic(&path, json.as_bytes())
+    }
+
+    /// The data file to open: `--data` wins over `TODO_GUI_DATA`, which wins
+    /// over `data_path` and finally the default location for `storage`.
+    pub fn resolve_data_path(&self, arg: Option<PathBuf>, env: Option<PathBuf>) -> PathBuf {
+        arg.or(env.filter(|path| !path.as_os_str().is_empty()))
+            .or_else(|| self.data_path.clone())
+            .unwrap_or_else(|| storage::default_path(self.storage))
+    }
+
+    /// Moves `path` to the front of the recent workspaces.
+    pub fn remember_workspace(&mut self, path: PathBuf) {
+        self.recent_workspaces.retain(|recent| *recent != path);
+        self.recent_workspaces.insert(0, path);
+        self.recent_workspaces.truncate(MAX_RECENT_WORKSPACES);
+    }
+}
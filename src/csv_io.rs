@@ -0,0 +1,632 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+use crate::error::{Result, TodoError};
+use crate::recurrence::Recurrence;
+use crate::todo::{end_of_day, Priority, Subtask, Todo, TodoDocument, TodoId, TodoList};
+
+/// A `Todo` field a CSV column can hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Id,
+    Title,
+    Description,
+    Completed,
+    CreatedAt,
+    CompletedAt,
+    UpdatedAt,
+    Due,
+    Priority,
+    Tags,
+    /// The name of the list, lists missing from the document are created
+    List,
+    Subtasks,
+    Recurrence,
+    /// The column is not imported
+    Ignore,
+}
+
+impl Field {
+    /// The exported columns, in order.
+    pub const COLUMNS: [Field; 13] = [
+        Field::Id,
+        Field::Title,
+        Field::Description,
+        Field::Completed,
+        Field::CreatedAt,
+        Field::CompletedAt,
+        Field::UpdatedAt,
+        Field::Due,
+        Field::Priority,
+        Field::Tags,
+        Field::List,
+        Field::Subtasks,
+        Field::Recurrence,
+    ];
+
+    pub const ALL: [Field; 14] = [
+        Field::Ignore,
+        Field::Id,
+        Field::Title,
+        Field::Description,
+        Field::Completed,
+        Field::CreatedAt,
+        Field::CompletedAt,
+        Field::UpdatedAt,
+        Field::Due,
+        Field::Priority,
+        Field::Tags,
+        Field::List,
+        Field::Subtasks,
+        Field::Recurrence,
+    ];
+
+    /// The header written on export.
+    pub fn header(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Title => "title",
+            Field::Description => "description",
+            Field::Completed => "completed",
+            Field::CreatedAt => "created_at",
+            Field::CompletedAt => "completed_at",
+            Field::UpdatedAt => "updated_at",
+            Field::Due => "due",
+            Field::Priority => "priority",
+            Field::Tags => "tags",
+            Field::List => "list",
+            Field::Subtasks => "subtasks",
+            Field::Recurrence => "recurrence",
+            Field::Ignore => "",
+        }
+    }
+
+    /// The field a column is mapped to before the user changes it, matched
+    /// on the exported headers and a few common spreadsheet names.
+    pub fn guess(header: &str) -> Field {
+        let header = header.trim().to_lowercase().replace([' ', '-'], "_");
+        if let Some(field) = Self::COLUMNS.into_iter().find(|field| field.header() == header) {
+            return field;
+        }
+        match header.as_str() {
+            "name" | "task" | "todo" | "summary" | "subject" => Field::Title,
+            "notes" | "note" | "details" => Field::Description,
+            "done" | "status" | "finished" => Field::Completed,
+            "created" | "date" | "start" => Field::CreatedAt,
+            "completed_on" | "done_at" => Field::CompletedAt,
+            "updated" | "modified" | "last_modified" => Field::UpdatedAt,
+            "due_date" | "deadline" | "due_at" => Field::Due,
+            "prio" | "importance" => Field::Priority,
+            "tag" | "labels" | "label" | "categories" | "category" => Field::Tags,
+            "project" | "list_name" => Field::List,
+            "checklist" => Field::Subtasks,
+            "repeat" | "recurring" => Field::Recurrence,
+            _ => Field::Ignore,
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Field::Id => "Id",
+            Field::Title => "Title",
+            Field::Description => "Description",
+            Field::Completed => "Completed",
+            Field::CreatedAt => "Created at",
+            Field::CompletedAt => "Completed at",
+            Field::UpdatedAt => "Updated at",
+            Field::Due => "Due date",
+            Field::Priority => "Priority",
+            Field::Tags => "Tags",
+            Field::List => "List",
+            Field::Subtasks => "Subtasks",
+            Field::Recurrence => "Repeats",
+            Field::Ignore => "(ignore)",
+        })
+    }
+}
+
+/// Which todos are exported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportScope {
+    /// Every todo that is not in the trash or the archive
+    All,
+    /// The todos currently shown
+    Filtered,
+}
+
+/// Writes `todos` with a header row of `Field::COLUMNS`. Timestamps are
+/// RFC 3339, tags are separated by spaces and subtasks by `; ` with a
+/// `[x] ` or `[ ] ` prefix, a `;` or `\` in their titles escaped with `\`.
+/// Cells a spreadsheet would run as a formula start with `'`.
+pub fn export(todos: &[&Todo], lists: &BTreeMap<usize, TodoList>) -> Result<String> {
+    let csv_error = |e: csv::Error| TodoError::CsvError(e.to_string());
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(Field::COLUMNS.map(Field::header))
+        .map_err(csv_error)?;
+
+    for todo in todos {
+        writer.write_record(record(todo, lists)).map_err(csv_error)?;
+    }
+
+    let data = writer.into_inner().map_err(|e| TodoError::CsvError(e.to_string()))?;
+    String::from_utf8(data).map_err(|e| TodoError::CsvError(e.to_string()))
+}
+
+/// The cells of `todo`, in the order of `Field::COLUMNS`.
+fn record(todo: &Todo, lists: &BTreeMap<usize, TodoList>) -> [String; 13] {
+    let time = |time: Option<DateTime<Local>>| time.map(|time| time.to_rfc3339()).unwrap_or_default();
+    let subtasks: Vec<String> = todo
+        .subtasks
+        .iter()
+        .map(|subtask| {
+            let title = subtask.title.replace('\\', "\\\\").replace(';', "\\;");
+            format!("[{}] {}", if subtask.completed { "x" } else { " " }, title)
+        })
+        .collect();
+    [
+        todo.id.to_string(),
+        todo.title.clone(),
+        todo.description.clone(),
+        todo.completed.to_string(),
+        todo.created_at.to_rfc3339(),
+        time(todo.completed_at),
+        time(todo.updated_at),
+        time(todo.due),
+        priority_name(todo.priority).to_string(),
+        todo.tags.iter().cloned().collect::<Vec<_>>().join(" "),
+        lists.get(&todo.list_id).map(|list| list.name.clone()).unwrap_or_default(),
+        subtasks.join("; "),
+        todo.recurrence.as_ref().map(ToString::to_string).unwrap_or_default(),
+    ]
+    .map(guard_formula)
+}
+
+/// First characters that make spreadsheets read a cell as a formula.
+const FORMULA_STARTS: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Prefixes a cell that a spreadsheet would run as a formula with `'`, which
+/// spreadsheets show as text and hide. A cell starting with `'` gets one
+/// too, so `unguard_formula` gives back exactly what was written.
+fn guard_formula(cell: String) -> String {
+    if cell.starts_with(FORMULA_STARTS) || cell.starts_with('\'') {
+        format!("'{}", cell)
+    } else {
+        cell
+    }
+}
+
+/// Drops the `'` that `guard_formula` put in front of a cell.
+fn unguard_formula(cell: &str) -> &str {
+    match cell.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(FORMULA_STARTS) || rest.starts_with('\'') => rest,
+        _ => cell,
+    }
+}
+
+fn priority_name(priority: Priority) -> &'static str {
+    match priority {
+        Priority::None => "",
+        Priority::Low => "low",
+        Priority::Medium => "medium",
+        Priority::High => "high",
+        Priority::Urgent => "urgent",
+    }
+}
+
+/// The cells of a CSV file, the first row taken as headers.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CsvTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    /// Reads comma or semicolon separated data, whichever the header row
+    /// uses more of. Spreadsheets in many locales save with semicolons.
+    pub fn parse(data: &str) -> Result<Self> {
+        let data = data.strip_prefix('\u{feff}').unwrap_or(data);
+        let header_line = data.lines().next().unwrap_or_default();
+        let delimiter = if header_line.matches(';').count() > header_line.matches(',').count() {
+            b';'
+        } else {
+            b','
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .has_headers(false)
+            .from_reader(data.as_bytes());
+        let mut records = reader.records();
+        let headers = match records.next() {
+            Some(record) => record
+                .map_err(|e| TodoError::CsvError(e.to_string()))?
+                .iter()
+                .map(str::to_string)
+                .collect(),
+            None => return Err(TodoError::CsvError(String::from("the file is empty"))),
+        };
+        let rows = records
+            .map(|record| {
+                record
+                    .map(|record| record.iter().map(str::to_string).collect())
+                    .map_err(|e| TodoError::CsvError(e.to_string()))
+            })
+            .collect::<Result<Vec<Vec<String>>>>()?;
+
+        Ok(Self {
+            headers,
+            // Spreadsheets tend to leave empty rows at the end
+            rows: rows
+                .into_iter()
+                .filter(|row| row.iter().any(|cell| !cell.trim().is_empty()))
+                .collect(),
+        })
+    }
+
+    /// A table as `export` would write it, so todos read from another
+    /// format go through the same import wizard.
+    pub fn from_todos(todos: &[&Todo], lists: &BTreeMap<usize, TodoList>) -> Self {
+        Self {
+            headers: Field::COLUMNS.iter().map(|field| field.header().to_string()).collect(),
+            rows: todos.iter().map(|todo| record(todo, lists).to_vec()).collect(),
+        }
+    }
+}
+
+/// What happens to an imported todo that matches one already present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateAction {
+    /// Leave the existing todo alone and drop the row
+    #[default]
+    Skip,
+    /// Overwrite the mapped fields of the existing todo
+    Update,
+    /// Import the row as a new todo regardless
+    KeepBoth,
+}
+
+impl DuplicateAction {
+    pub const ALL: [DuplicateAction; 3] = [
+        DuplicateAction::Skip,
+        DuplicateAction::Update,
+        DuplicateAction::KeepBoth,
+    ];
+}
+
+impl fmt::Display for DuplicateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DuplicateAction::Skip => "Skip duplicates",
+            DuplicateAction::Update => "Update duplicates",
+            DuplicateAction::KeepBoth => "Import duplicates too",
+        })
+    }
+}
+
+/// A row of the CSV file as it would be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    /// Line in the file, counting the header as line 1
+    pub line: usize,
+    /// The todo read from the row, or why it cannot be imported
+    pub todo: std::result::Result<Todo, String>,
+    /// Name of the list the todo goes into, `None` for the inbox
+    pub list: Option<String>,
+    /// The todo the row matches, by id or else by title
+    pub duplicate: Option<TodoId>,
+}
+
+/// The CSV import wizard: the file read, which field each column maps to and
+/// how duplicates are treated.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImportWizard {
+    pub table: CsvTable,
+    /// Field of each column, by position
+    pub mapping: Vec<Field>,
+    pub duplicates: DuplicateAction,
+}
+
+impl ImportWizard {
+    /// Starts with the mapping guessed from the headers. A column is only
+    /// guessed for a field no earlier column already took.
+    pub fn new(table: CsvTable) -> Self {
+        let mut taken = HashSet::new();
+        let mapping = table
+            .headers
+            .iter()
+            .map(|header| match Field::guess(header) {
+                Field::Ignore => Field::Ignore,
+                field if taken.insert(field) => field,
+                _ => Field::Ignore,
+            })
+            .collect();
+        Self {
+            table,
+            mapping,
+            duplicates: DuplicateAction::default(),
+        }
+    }
+
+    /// Maps column `index` to `field`, unmapping any other column holding it.
+    pub fn set_field(&mut self, index: usize, field: Field) {
+        if field != Field::Ignore {
+            for mapped in self.mapping.iter_mut().filter(|mapped| **mapped == field) {
+                *mapped = Field::Ignore;
+            }
+        }
+        if let Some(mapped) = self.mapping.get_mut(index) {
+            *mapped = field;
+        }
+    }
+
+    /// The fields some column maps to.
+    pub fn mapped_fields(&self) -> Vec<Field> {
+        self.mapping
+            .iter()
+            .copied()
+            .filter(|field| *field != Field::Ignore)
+            .collect()
+    }
+
+    /// Reads every row with the current mapping and looks for duplicates in
+    /// `document` and in the rows before it. Trashed todos do not count.
+    pub fn preview(&self, document: &TodoDocument, now: DateTime<Local>) -> Vec<ImportRow> {
+        let mut ids: BTreeSet<TodoId> = document
+            .todos
+            .values()
+            .filter(|todo| !todo.is_trashed())
+            .map(|todo| todo.id)
+            .collect();
+        let mut titles: HashMap<String, TodoId> = document
+            .todos
+            .values()
+            .filter(|todo| !todo.is_trashed())
+            .map(|todo| (title_key(&todo.title), todo.id))
+            .collect();
+
+        self.table
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                let (todo, list) = match self.read_row(row, now) {
+                    Ok((todo, list)) => (Ok(todo), list),
+                    Err(error) => (Err(error), None),
+                };
+                let duplicate = todo.as_ref().ok().and_then(|todo| {
+                    let duplicate = if ids.contains(&todo.id) {
+                        Some(todo.id)
+                    } else {
+                        titles.get(&title_key(&todo.title)).copied()
+                    };
+                    ids.insert(todo.id);
+                    titles.entry(title_key(&todo.title)).or_insert(todo.id);
+                    duplicate
+                });
+                ImportRow {
+                    line: index + 2,
+                    todo,
+                    list,
+                    duplicate,
+                }
+            })
+            .collect()
+    }
+
+    /// A todo from a row plus the name of its list. Unmapped fields get the
+    /// values a new todo has, an id that is missing or not a UUID a new one.
+    fn read_row(
+        &self,
+        row: &[String],
+        now: DateTime<Local>,
+    ) -> std::result::Result<(Todo, Option<String>), String> {
+        let mut todo = Todo {
+            id: TodoId::random(),
+            title: String::new(),
+            description: String::new(),
+            completed: false,
+            created_at: now,
+            completed_at: None,
+            updated_at: None,
+            due: None,
+            priority: Priority::None,
+            tags: BTreeSet::new(),
+            subtasks: Vec::new(),
+            recurrence: None,
+            list_id: crate::todo::INBOX_LIST_ID,
+            series: None,
+            deleted_at: None,
+            activity: Vec::new(),
+        };
+        let mut list = None;
+        let mut completed = None;
+
+        for (field, cell) in self.mapping.iter().zip(row) {
+            let cell = unguard_formula(cell.trim());
+            if cell.is_empty() {
+                continue;
+            }
+            let invalid = |what: &str| format!("{}: \"{}\" is not {}", field, cell, what);
+            match field {
+                Field::Id => {
+                    if let Ok(id) = cell.parse() {
+                        todo.id = id;
+                    }
+                }
+                Field::Title => todo.title = cell.to_string(),
+                Field::Description => todo.description = cell.to_string(),
+                Field::Completed => {
+                    completed = Some(parse_bool(cell).ok_or_else(|| invalid("yes or no"))?);
+                }
+                Field::CreatedAt => {
+                    todo.created_at = parse_time(cell, false).ok_or_else(|| invalid("a date"))?;
+                }
+                Field::CompletedAt => {
+                    todo.completed_at = Some(parse_time(cell, false).ok_or_else(|| invalid("a date"))?);
+                }
+                Field::UpdatedAt => {
+                    todo.updated_at = Some(parse_time(cell, false).ok_or_else(|| invalid("a date"))?);
+                }
+                Field::Due => todo.due = Some(parse_time(cell, true).ok_or_else(|| invalid("a date"))?),
+                Field::Priority => {
+                    todo.priority = parse_priority(cell).ok_or_else(|| invalid("a priority"))?;
+                }
+                Field::Tags => {
+                    todo.tags = cell
+                        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                        .map(|tag| tag.trim_start_matches('#').to_lowercase())
+                        .filter(|tag| !tag.is_empty())
+                        .collect();
+                }
+                Field::List => list = Some(cell.to_string()),
+                Field::Subtasks => todo.subtasks = parse_subtasks(cell),
+                Field::Recurrence => todo.recurrence = Some(cell.parse::<Recurrence>()?),
+                Field::Ignore => {}
+            }
+        }
+
+        if todo.title.is_empty() {
+            return Err(String::from("no title"));
+        }
+        todo.completed = completed.unwrap_or(todo.completed_at.is_some());
+        if !todo.completed {
+            todo.completed_at = None;
+        }
+        if todo.recurrence.is_some() && todo.due.is_none() {
+            todo.due = end_of_day(todo.created_at.date_naive());
+        }
+        Ok((todo, list))
+    }
+}
+
+/// The panel of the todo view that exports to and imports from a CSV file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CsvPanel {
+    /// The file typed into the panel
+    pub path: String,
+    /// Outcome of the last export or why reading the file failed
+    pub status: Option<String>,
+    /// The file being imported, `None` until one is read
+    pub import: Option<ImportWizard>,
+}
+
+/// Copies the mapped `fields` of an imported todo onto the duplicate it
+/// updates. The id stays, completion and its time go together.
+pub fn update_fields(target: &mut Todo, source: &Todo, fields: &[Field]) {
+    for field in fields {
+        match field {
+            Field::Title => target.title = source.title.clone(),
+            Field::Description => target.description = source.description.clone(),
+            Field::Completed | Field::CompletedAt => {
+                target.completed = source.completed;
+                target.completed_at = source.completed_at;
+            }
+            Field::CreatedAt => target.created_at = source.created_at,
+            Field::UpdatedAt => target.updated_at = source.updated_at,
+            Field::Due => target.due = source.due,
+            Field::Priority => target.priority = source.priority,
+            Field::Tags => target.tags = source.tags.clone(),
+            Field::List => target.list_id = source.list_id,
+            Field::Subtasks => target.subtasks = source.subtasks.clone(),
+            Field::Recurrence => target.recurrence = source.recurrence.clone(),
+            Field::Id | Field::Ignore => {}
+        }
+    }
+}
+
+/// Titles are compared ignoring case and surrounding whitespace.
+fn title_key(title: &str) -> String {
+    title.trim().to_lowercase()
+}
+
+fn parse_bool(cell: &str) -> Option<bool> {
+    match cell.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" | "x" | "done" | "completed" => Some(true),
+        "false" | "no" | "n" | "0" | "open" | "todo" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_priority(cell: &str) -> Option<Priority> {
+    let cell = cell.to_lowercase();
+    if let Ok(level) = cell.parse::<usize>() {
+        return Priority::ALL.get(level).copied();
+    }
+    Priority::ALL.into_iter().find(|priority| {
+        priority_name(*priority) == cell || priority.to_string().to_lowercase() == cell
+    })
+}
+
+/// Accepts RFC 3339 and the date and time formats spreadsheets commonly
+/// write. A date without a time is the start of the day, or for due dates
+/// the end of it.
+fn parse_time(cell: &str, due: bool) -> Option<DateTime<Local>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(cell) {
+        return Some(time.with_timezone(&Local));
+    }
+    const DATE_TIMES: [&str; 4] = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%d.%m.%Y %H:%M",
+    ];
+    if let Some(time) = DATE_TIMES
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(cell, format).ok())
+    {
+        return time.and_local_timezone(Local).earliest();
+    }
+    let date = ["%Y-%m-%d", "%d.%m.%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(cell, format).ok())?;
+    if due {
+        end_of_day(date)
+    } else {
+        date.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest()
+    }
+}
+
+/// Reads subtasks as exported, items without a `[x] ` or `[ ] ` prefix
+/// count as open.
+fn parse_subtasks(cell: &str) -> Vec<Subtask> {
+    split_subtasks(cell)
+        .iter()
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .enumerate()
+        .map(|(index, item)| {
+            let done = item.strip_prefix("[x]").or_else(|| item.strip_prefix("[X]"));
+            let (completed, title) = match done {
+                Some(title) => (true, title),
+                None => (false, item.strip_prefix("[ ]").unwrap_or(item)),
+            };
+            Subtask {
+                id: index + 1,
+                title: title.trim().to_string(),
+                completed,
+            }
+        })
+        .collect()
+}
+
+/// Splits at each `;` not escaped with `\`, undoing the escapes. Other
+/// backslashes are kept, as in files written before subtasks were escaped.
+fn split_subtasks(cell: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut chars = cell.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some('\\' | ';')) => item.extend(chars.next()),
+            ';' => items.push(std::mem::take(&mut item)),
+            _ => item.push(c),
+        }
+    }
+    items.push(item);
+    items
+}
@@ -1,13 +1,21 @@
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum TodoError {
     #[error("Failed to save todos: {0}")]
     SaveError(String),
     #[error("Failed to load todos: {0}")]
     LoadError(String),
-    #[error("UI error: {0}")]
-    UIError(#[from] iced::Error),
+    #[error("Failed to parse todos: {0}")]
+    ParseError(String),
+    #[error("Todo file has version {found}, but this app only supports up to version {supported}")]
+    UnsupportedVersion { found: u32, supported: u32 },
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("Encryption failed: {0}")]
+    CryptoError(String),
+    #[error("Import or export failed: {0}")]
+    CsvError(String),
 }
 
-pub type Result<T> = std::result::Result<T, TodoError>; 
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, TodoError>;
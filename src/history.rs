@@ -0,0 +1,49 @@
+use crate::todo::TodoDocument;
+
+/// How many steps can be undone before the oldest one is dropped.
+const MAX_STEPS: usize = 100;
+
+/// Undo/redo stacks of whole document snapshots.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<TodoDocument>,
+    redo: Vec<TodoDocument>,
+}
+
+impl History {
+    /// Records the document as it was before a change. A new change makes
+    /// the redo stack unreachable, so it is cleared.
+    pub fn record(&mut self, before: TodoDocument) {
+        self.redo.clear();
+        self.undo.push(before);
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+        }
+    }
+
+    /// Swaps `current` for the previous snapshot.
+    pub fn undo(&mut self, current: TodoDocument) -> Option<TodoDocument> {
+        let previous = self.undo.pop()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    /// Swaps `current` for the snapshot undone last.
+    pub fn redo(&mut self, current: TodoDocument) -> Option<TodoDocument> {
+        let next = self.redo.pop()?;
+        self.undo.push(current);
+        Some(next)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -1,70 +1,897 @@
 use iced::{
-    Application, Command, Element, Theme, executor,
+    keyboard::{self, KeyCode},
+    widget::column,
+    window, Application, Command, Element, Event, Subscription, Theme, executor,
 };
-use todo::TodoState;
-use ui::view;
+use config::Config;
+use csv_io::{CsvTable, ExportScope};
+use error::TodoError;
+use lock::WorkspaceLock;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use storage::{ChangeSet, EncryptedStorage, FileStamp, Storage, StorageKind};
+use todo::{Filter, Todo, TodoDocument, TodoState};
+use ui::{banner, unlock, view, workspace};
+use zeroize::Zeroizing;
 
+pub mod activity;
+pub mod config;
+pub mod csv_io;
+pub mod error;
+pub mod history;
+pub mod lock;
+pub mod merge;
+pub mod migrations;
+pub mod recurrence;
+pub mod storage;
 pub mod todo;
+pub mod todotxt;
 pub mod ui;
 
+/// How long the document has to stay unchanged before it is saved, so typing
+/// a title or description does not write the file on every keystroke.
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(1000);
+const SAVE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// Wait before retrying a failed save, doubled with every failure in a row
+/// up to `MAX_SAVE_RETRY`.
+const SAVE_RETRY: Duration = Duration::from_secs(2);
+const MAX_SAVE_RETRY: Duration = Duration::from_secs(60);
+/// How often the data file is checked for changes made outside the app.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+const AUTO_LOCK_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+/// Shorter passphrases are refused when encrypting.
+const MIN_PASSPHRASE_LEN: usize = 8;
+
 pub struct TodoApp {
     todo_state: TodoState,
+    config: Config,
+    /// The data file being edited
+    workspace: PathBuf,
+    storage: Arc<dyn Storage>,
+    /// Changes handed to the storage that have not been confirmed saved yet
+    unsaved: ChangeSet,
+    /// When the document was last changed, `None` once it has been saved
+    last_change: Option<Instant>,
+    /// Whether a background save is running
+    saving: bool,
+    /// The window is waiting for the running save before it closes
+    closing: bool,
+    /// Saving failed while closing, the next close request closes regardless
+    force_close: bool,
+    load_error: Option<TodoError>,
+    save_error: Option<TodoError>,
+    /// Saves that failed in a row, to back off retrying
+    save_failures: u32,
+    /// Set while `todos.json` exists but could not be loaded, so the default
+    /// state shown in its place never overwrites it.
+    save_blocked: bool,
+    backups: Vec<PathBuf>,
+    /// The document as last read from or written to disk, the common base
+    /// when merging changes made outside the app
+    synced: Option<TodoDocument>,
+    /// The data file as last read or written
+    disk_stamp: Option<FileStamp>,
+    /// Whether the data file is being read again after it changed on disk
+    reloading: bool,
+    /// Path typed into the open workspace menu, `None` while it is closed
+    workspace_menu: Option<String>,
+    /// Held while this window may save the data file
+    lock: Option<WorkspaceLock>,
+    /// Another instance holds the lock, nothing is saved
+    read_only: bool,
+    encryption: Encryption,
+    passphrase: PassphraseForm,
+    /// When the user last did something, to lock after inactivity
+    last_activity: Instant,
+}
+
+/// Whether the open workspace is encrypted and its key known.
+#[derive(Debug, Clone)]
+pub enum Encryption {
+    Plain,
+    /// Waiting for the passphrase, nothing is loaded or saved
+    Locked,
+    Unlocked(Arc<EncryptedStorage>),
+}
+
+/// Passphrase fields of the unlock screen and the encryption menu. The
+/// passphrases are wiped from memory once they are replaced or used.
+#[derive(Debug, Default)]
+pub struct PassphraseForm {
+    pub passphrase: Zeroizing<String>,
+    pub confirm: Zeroizing<String>,
+    pub error: Option<String>,
+    /// A key is being derived and the files rewritten, which takes a moment
+    pub working: bool,
+}
+
+/// Options given on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct Flags {
+    /// `--data <path>`: the data file to open
+    pub data_path: Option<PathBuf>,
+}
+
+impl Flags {
+    /// Reads `--data <path>` and `--data=<path>`, ignoring anything else.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Self {
+        let mut flags = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--data" {
+                flags.data_path = args.next().map(PathBuf::from);
+            } else if let Some(path) = arg.strip_prefix("--data=") {
+                flags.data_path = Some(PathBuf::from(path));
+            } else {
+                log::warn!("Ignoring unknown argument {}", arg);
+            }
+        }
+        flags
+    }
+}
+
+/// What a save wrote and the data file it left behind.
+#[derive(Debug, Clone)]
+pub struct Saved {
+    document: TodoDocument,
+    stamp: Option<FileStamp>,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     TodoMessage(todo::Message),
     LoadTodos,
-    TodosLoaded(Result<TodoState, String>),
+    TodosLoaded(Result<TodoDocument, TodoError>),
     SaveTodos,
-    TodosSaved(Result<(), String>),
+    TodosSaved(Result<Saved, TodoError>),
+    ArchiveLoaded(Result<Vec<Todo>, TodoError>),
+    RestoreBackup(PathBuf),
+    BackupRestored(Result<TodoDocument, TodoError>),
+    /// Gives up on the unreadable file and lets the next save replace it
+    OverwriteUnreadable,
+    DismissSaveError,
+    /// Periodic check whether the debounced save is due
+    SaveTick(Instant),
+    /// Periodic check whether the data file was changed outside the app
+    CheckDataFile(Instant),
+    ExternalChange(Result<Option<TodoDocument>, TodoError>),
+    CloseRequested,
+    ToggleWorkspaceMenu,
+    WorkspacePathChanged(String),
+    /// Saves the open data file and switches to another one
+    OpenWorkspace(PathBuf),
+    /// Tries again to take the lock of a workspace opened read-only
+    RetryLock,
+    PassphraseChanged(String),
+    PassphraseConfirmChanged(String),
+    Unlock,
+    EncryptWorkspace,
+    ChangePassphrase,
+    DecryptWorkspace,
+    /// The key of the workspace was derived, `None` after decrypting it
+    EncryptionChanged(Result<Option<Arc<EncryptedStorage>>, TodoError>),
+    /// Forgets the key and the todos until the passphrase is entered again
+    LockWorkspace,
+    /// Periodic check whether the workspace has been idle long enough to lock
+    AutoLockTick(Instant),
+    /// The number of todos written to the CSV file
+    CsvExported(Result<usize, TodoError>),
+    CsvRead(Result<CsvTable, TodoError>),
 }
 
 impl Application for TodoApp {
     type Executor = executor::Default;
     type Message = Message;
     type Theme = Theme;
-    type Flags = ();
+    type Flags = Flags;
 
-    fn new(_flags: Self::Flags) -> (Self, Command<Message>) {
-        (
-            Self {
-                todo_state: TodoState::default(),
-            },
-            Command::perform(async {}, |_| Message::LoadTodos),
-        )
+    fn new(flags: Self::Flags) -> (Self, Command<Message>) {
+        let mut config = Config::load();
+        let env = std::env::var_os(config::DATA_PATH_VAR).map(PathBuf::from);
+        let workspace = config.resolve_data_path(flags.data_path, env);
+        if config.recent_workspaces.first() != Some(&workspace) {
+            config.remember_workspace(workspace.clone());
+            if let Err(e) = config.save() {
+                log::warn!("Could not save config file: {}", e);
+            }
+        }
+
+        let mut app = Self {
+            todo_state: TodoState::default(),
+            storage: storage::open(&workspace),
+            config,
+            workspace,
+            unsaved: ChangeSet::default(),
+            last_change: None,
+            saving: false,
+            closing: false,
+            force_close: false,
+            load_error: None,
+            save_error: None,
+            save_failures: 0,
+            save_blocked: false,
+            backups: Vec::new(),
+            synced: None,
+            disk_stamp: None,
+            reloading: false,
+            workspace_menu: None,
+            lock: None,
+            read_only: false,
+            encryption: Encryption::Plain,
+            passphrase: PassphraseForm::default(),
+            last_activity: Instant::now(),
+        };
+        app.lock_workspace();
+        app.detect_encryption();
+
+        (app, Command::perform(async {}, |_| Message::LoadTodos))
     }
 
     fn title(&self) -> String {
-        String::from("Todo App")
+        if self.read_only {
+            String::from("Todo App (read-only)")
+        } else {
+            String::from("Todo App")
+        }
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
             Message::TodoMessage(msg) => {
+                if !matches!(msg, todo::Message::NoticeTick(_)) {
+                    self.last_activity = Instant::now();
+                }
+                let opens_archive = matches!(msg, todo::Message::FilterChanged(Filter::Archive));
+                match msg {
+                    todo::Message::ExportActivity(id) => {
+                        return match self.todo_state.activity_json(id) {
+                            Some(json) => iced::clipboard::write(json),
+                            None => Command::none(),
+                        };
+                    }
+                    todo::Message::ExportCsv(scope) => return self.export_csv(scope),
+                    todo::Message::ImportCsv => return self.read_csv(),
+                    _ => {}
+                }
                 self.todo_state.update(msg);
-                Command::perform(async {}, |_| Message::SaveTodos)
+                if self.todo_state.has_changes() {
+                    self.last_change = Some(Instant::now());
+                }
+
+                if opens_archive && !self.todo_state.archive_loaded() {
+                    let storage = self.storage.clone();
+                    Command::perform(async move { storage.load_archive() }, Message::ArchiveLoaded)
+                } else {
+                    Command::none()
+                }
             }
+            Message::ArchiveLoaded(archive) => {
+                self.todo_state.set_archive(archive);
+                Command::none()
+            }
+            Message::SaveTick(now) => match self.last_change {
+                Some(changed) if now.duration_since(changed) >= SAVE_DEBOUNCE && !self.saving => {
+                    self.update(Message::SaveTodos)
+                }
+                _ => Command::none(),
+            },
             Message::LoadTodos => {
-                Command::perform(TodoState::load(), Message::TodosLoaded)
+                if self.is_locked() {
+                    return Command::none();
+                }
+                if self.synced.is_some() {
+                    // Keep the edits made since and merge with the file instead
+                    return self.reload();
+                }
+                self.disk_stamp = self.watched_stamp();
+                let storage = self.storage.clone();
+                Command::perform(
+                    async move { storage.load().map(Option::unwrap_or_default) },
+                    Message::TodosLoaded,
+                )
             }
-            Message::TodosLoaded(Ok(state)) => {
-                self.todo_state = state;
+            Message::TodosLoaded(Ok(document)) => {
+                self.synced = Some(document.clone());
+                self.todo_state.set_document(document);
+                if !self.read_only {
+                    let now = chrono::Local::now();
+                    self.todo_state.purge_trash(now);
+                    self.todo_state.auto_archive(now);
+                }
+                if self.todo_state.has_changes() {
+                    self.last_change = Some(Instant::now());
+                }
+                self.load_error = None;
+                self.save_blocked = false;
                 Command::none()
             }
-            Message::TodosLoaded(Err(_)) => {
-                // If loading fails, we'll just keep the default state
+            Message::TodosLoaded(Err(error)) => {
+                // Keep the default state, but never save it over the file
+                // that failed to load, and offer to restore a backup
+                self.load_error = Some(error);
+                self.save_blocked = true;
+                self.backups = self.storage.backups();
                 Command::none()
             }
             Message::SaveTodos => {
-                let cloned_state = self.todo_state.clone();
-                Command::perform(async move { cloned_state.save().await }, Message::TodosSaved)
+                if self.save_blocked
+                    || self.saving
+                    || self.reloading
+                    || self.read_only
+                    || self.is_locked()
+                    || self.passphrase.working
+                {
+                    return Command::none();
+                }
+                if self.changed_on_disk() {
+                    // Merge first so the save does not overwrite the change
+                    return self.reload();
+                }
+                self.last_change = None;
+                self.unsaved.merge(self.todo_state.take_changes());
+                if self.unsaved.is_empty() {
+                    return Command::none();
+                }
+
+                self.saving = true;
+                let storage = self.storage.clone();
+                let document = self.todo_state.document().clone();
+                let changes = self.unsaved.clone();
+                Command::perform(
+                    async move {
+                        storage.save_changes(&document, &changes)?;
+                        let stamp = storage.data_path().and_then(FileStamp::of);
+                        Ok(Saved { document, stamp })
+                    },
+                    Message::TodosSaved,
+                )
+            }
+            Message::TodosSaved(result) => {
+                self.saving = false;
+                match result {
+                    Ok(saved) => {
+                        self.unsaved = ChangeSet::default();
+                        self.save_error = None;
+                        self.save_failures = 0;
+                        self.synced = Some(saved.document);
+                        self.disk_stamp = saved.stamp;
+                    }
+                    Err(error) => {
+                        // The file may be locked or the disk full for a moment,
+                        // so try again later. The tick also waits out the debounce.
+                        let retry = SAVE_RETRY.saturating_mul(1 << self.save_failures.min(5));
+                        self.save_failures += 1;
+                        self.last_change = Some(Instant::now() + retry.min(MAX_SAVE_RETRY));
+                        self.save_error = Some(error);
+                    }
+                }
+
+                if self.closing {
+                    self.update(Message::CloseRequested)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::CheckDataFile(_) => {
+                if self.saving
+                    || self.reloading
+                    || self.is_locked()
+                    || self.passphrase.working
+                    || !self.changed_on_disk()
+                {
+                    return Command::none();
+                }
+                self.reload()
+            }
+            Message::ExternalChange(result) => {
+                self.reloading = false;
+                match result {
+                    Ok(Some(document)) if self.synced.is_none() => {
+                        return self.update(Message::TodosLoaded(Ok(document)));
+                    }
+                    Ok(Some(document)) => {
+                        self.merge_external(document);
+                        self.load_error = None;
+                        self.save_blocked = false;
+                        self.backups.clear();
+                    }
+                    Ok(None) => {
+                        // Removed from disk, the next save writes it again
+                        self.unsaved.merge(ChangeSet::full());
+                        self.last_change = Some(Instant::now());
+                    }
+                    Err(error) => {
+                        self.load_error = Some(error);
+                        self.save_blocked = true;
+                        self.backups = self.storage.backups();
+                    }
+                }
+                Command::none()
+            }
+            Message::ToggleWorkspaceMenu => {
+                self.workspace_menu = match self.workspace_menu {
+                    Some(_) => None,
+                    None => Some(String::new()),
+                };
+                Command::none()
+            }
+            Message::WorkspacePathChanged(path) => {
+                self.workspace_menu = Some(path);
+                Command::none()
+            }
+            Message::OpenWorkspace(path) => {
+                // Wait for running saves and reloads, their results belong
+                // to the open workspace
+                if path.as_os_str().is_empty()
+                    || self.saving
+                    || self.reloading
+                    || self.passphrase.working
+                {
+                    return Command::none();
+                }
+                self.workspace_menu = None;
+                if path == self.workspace {
+                    return Command::none();
+                }
+                if let Err(error) = self.flush() {
+                    self.save_error = Some(error);
+                    return Command::none();
+                }
+
+                self.config.data_path = Some(path.clone());
+                self.config.remember_workspace(path.clone());
+                if let Err(e) = self.config.save() {
+                    log::warn!("Could not save config file: {}", e);
+                }
+                self.storage = storage::open(&path);
+                self.workspace = path;
+                self.todo_state = TodoState::default();
+                self.lock_workspace();
+                self.detect_encryption();
+                self.unsaved = ChangeSet::default();
+                self.last_change = None;
+                self.load_error = None;
+                self.save_error = None;
+                self.save_failures = 0;
+                self.save_blocked = false;
+                self.backups.clear();
+                self.synced = None;
+                self.disk_stamp = None;
+                self.update(Message::LoadTodos)
+            }
+            Message::RetryLock => {
+                self.lock_workspace();
+                if self.read_only {
+                    Command::none()
+                } else {
+                    // Pick up what the other window saved before closing
+                    self.update(Message::LoadTodos)
+                }
+            }
+            Message::PassphraseChanged(passphrase) => {
+                self.passphrase.passphrase = Zeroizing::new(passphrase);
+                Command::none()
+            }
+            Message::PassphraseConfirmChanged(confirm) => {
+                self.passphrase.confirm = Zeroizing::new(confirm);
+                Command::none()
+            }
+            Message::Unlock => {
+                if !self.is_locked() || self.passphrase.working {
+                    return Command::none();
+                }
+                let path = self.workspace.clone();
+                let passphrase = std::mem::take(&mut self.passphrase.passphrase);
+                self.start_key_derivation(move || {
+                    EncryptedStorage::unlock(path, &passphrase).map(|storage| Some(Arc::new(storage)))
+                })
+            }
+            Message::EncryptWorkspace => {
+                if !matches!(self.encryption, Encryption::Plain)
+                    || StorageKind::for_path(&self.workspace) != StorageKind::Json
+                    || self.read_only
+                {
+                    return Command::none();
+                }
+                let Some(passphrase) = self.new_passphrase() else {
+                    return Command::none();
+                };
+                let path = self.workspace.clone();
+                self.start_key_derivation(move || {
+                    EncryptedStorage::encrypt(path, &passphrase).map(|storage| Some(Arc::new(storage)))
+                })
+            }
+            Message::ChangePassphrase => {
+                let Encryption::Unlocked(storage) = self.encryption.clone() else {
+                    return Command::none();
+                };
+                if self.read_only {
+                    return Command::none();
+                }
+                let Some(passphrase) = self.new_passphrase() else {
+                    return Command::none();
+                };
+                self.start_key_derivation(move || {
+                    storage.change_passphrase(&passphrase).map(|storage| Some(Arc::new(storage)))
+                })
+            }
+            Message::DecryptWorkspace => {
+                let Encryption::Unlocked(storage) = self.encryption.clone() else {
+                    return Command::none();
+                };
+                if self.read_only {
+                    return Command::none();
+                }
+                self.start_key_derivation(move || storage.decrypt().map(|_| None))
+            }
+            Message::EncryptionChanged(result) => {
+                self.passphrase.working = false;
+                match result {
+                    Ok(encrypted) => {
+                        self.passphrase = PassphraseForm::default();
+                        self.last_activity = Instant::now();
+                        match encrypted {
+                            Some(storage) => {
+                                self.storage = storage.clone();
+                                self.encryption = Encryption::Unlocked(storage);
+                            }
+                            None => {
+                                self.storage = storage::open(&self.workspace);
+                                self.encryption = Encryption::Plain;
+                            }
+                        }
+                        self.update(Message::LoadTodos)
+                    }
+                    Err(error) => {
+                        self.passphrase.error = Some(error.to_string());
+                        Command::none()
+                    }
+                }
+            }
+            Message::LockWorkspace => {
+                if !matches!(self.encryption, Encryption::Unlocked(_)) || self.saving {
+                    return Command::none();
+                }
+                if let Err(error) = self.flush() {
+                    // Locking would lose the changes
+                    self.save_error = Some(error);
+                    return Command::none();
+                }
+                self.encryption = Encryption::Locked;
+                // Drops the key along with the storage holding it
+                self.storage = storage::open(&self.workspace);
+                self.todo_state = TodoState::default();
+                self.todo_state.set_read_only(self.read_only);
+                self.unsaved = ChangeSet::default();
+                self.last_change = None;
+                self.synced = None;
+                self.disk_stamp = None;
+                self.passphrase = PassphraseForm::default();
+                Command::none()
+            }
+            Message::AutoLockTick(now) => {
+                let idle = now.saturating_duration_since(self.last_activity);
+                let minutes = u64::from(self.config.auto_lock_minutes);
+                if minutes > 0 && idle >= Duration::from_secs(minutes * 60) {
+                    self.update(Message::LockWorkspace)
+                } else {
+                    Command::none()
+                }
+            }
+            Message::CsvExported(result) => {
+                self.todo_state.csv_exported(result);
+                Command::none()
+            }
+            Message::CsvRead(table) => {
+                self.todo_state.start_import(table);
+                Command::none()
+            }
+            Message::CloseRequested => {
+                if self.saving {
+                    // Finish the running save first, it may be older than ours
+                    self.closing = true;
+                    return Command::none();
+                }
+
+                self.closing = false;
+                match self.flush() {
+                    Ok(()) => window::close(),
+                    Err(_) if self.force_close => window::close(),
+                    Err(error) => {
+                        // Stay open so the error is seen
+                        self.save_error = Some(error);
+                        self.force_close = true;
+                        Command::none()
+                    }
+                }
+            }
+            Message::OverwriteUnreadable => {
+                self.load_error = None;
+                self.save_blocked = false;
+                self.backups.clear();
+                self.unsaved = ChangeSet::full();
+                Command::perform(async {}, |_| Message::SaveTodos)
+            }
+            Message::DismissSaveError => {
+                self.save_error = None;
+                Command::none()
+            }
+            Message::RestoreBackup(path) => {
+                let storage = self.storage.clone();
+                Command::perform(async move { storage.read_backup(&path) }, Message::BackupRestored)
+            }
+            Message::BackupRestored(Ok(document)) => {
+                self.todo_state.set_document(document);
+                self.load_error = None;
+                self.save_blocked = false;
+                self.backups.clear();
+                self.unsaved = ChangeSet::full();
+                Command::perform(async {}, |_| Message::SaveTodos)
+            }
+            Message::BackupRestored(Err(error)) => {
+                self.load_error = Some(error);
+                Command::none()
             }
-            Message::TodosSaved(_) => Command::none(),
         }
     }
 
-    fn view(&self) -> Element<Message> {
-        view::view_app(&self.todo_state).map(Message::TodoMessage)
+    fn subscription(&self) -> Subscription<Message> {
+        // Text inputs capture every key while focused, so the shortcuts are
+        // handled regardless of the event status
+        let events = iced::subscription::events_with(|event, _status| match event {
+            Event::Window(window::Event::CloseRequested) => Some(Message::CloseRequested),
+            Event::Keyboard(keyboard::Event::KeyPressed { key_code, modifiers })
+                if modifiers.command() =>
+            {
+                match key_code {
+                    KeyCode::Z if modifiers.shift() => Some(todo::Message::Redo),
+                    KeyCode::Z => Some(todo::Message::Undo),
+                    KeyCode::Y => Some(todo::Message::Redo),
+                    _ => None,
+                }
+                .map(Message::TodoMessage)
+            }
+            _ => None,
+        });
+
+        let mut subscriptions = vec![events];
+        if self.last_change.is_some() {
+            subscriptions.push(iced::time::every(SAVE_CHECK_INTERVAL).map(Message::SaveTick));
+        }
+        if self.storage.data_path().is_some() && !self.is_locked() {
+            subscriptions.push(iced::time::every(WATCH_INTERVAL).map(Message::CheckDataFile));
+        }
+        if matches!(self.encryption, Encryption::Unlocked(_)) && self.config.auto_lock_minutes > 0 {
+            subscriptions.push(iced::time::every(AUTO_LOCK_CHECK_INTERVAL).map(Message::AutoLockTick));
+        }
+        if self.todo_state.notice().is_some() {
+            subscriptions.push(
+                iced::time::every(Duration::from_secs(1))
+                    .map(|now| Message::TodoMessage(todo::Message::NoticeTick(now))),
+            );
+        }
+        Subscription::batch(subscriptions)
     }
-} 
\ No newline at end of file
+
+    fn view(&self) -> Element<'_, Message> {
+        let mut content = column![workspace::view_workspace_bar(
+            &self.workspace,
+            self.workspace_menu.as_deref(),
+            &self.config.recent_workspaces,
+        )];
+        let can_encrypt = StorageKind::for_path(&self.workspace) == StorageKind::Json;
+        if self.workspace_menu.is_some() && can_encrypt && !self.is_locked() {
+            content = content.push(
+                iced::widget::container(unlock::view_encryption_menu(&self.encryption, &self.passphrase, self.read_only))
+                    .padding([0, 20]),
+            );
+        }
+        if self.is_locked() {
+            return content.push(unlock::view_unlock(&self.passphrase)).into();
+        }
+
+        let app = view::view_app(&self.todo_state).map(Message::TodoMessage);
+        if self.read_only {
+            content = content.push(banner::view_read_only());
+        }
+        if let Some(error) = &self.load_error {
+            content = content.push(banner::view_load_error(error, &self.backups));
+        }
+        if let Some(error) = &self.save_error {
+            content = content.push(banner::view_save_error(error));
+        }
+        content.push(app).into()
+    }
+}
+
+impl TodoApp {
+    /// Synchronously writes everything not saved yet, used before closing.
+    fn flush(&mut self) -> Result<(), TodoError> {
+        if self.save_blocked || self.read_only || self.is_locked() {
+            return Ok(());
+        }
+
+        if self.changed_on_disk() {
+            // Conflicts keep the local version, there is no time to ask
+            if let Ok(Some(document)) = self.storage.load() {
+                self.merge_external(document);
+            }
+        }
+
+        self.last_change = None;
+        self.unsaved.merge(self.todo_state.take_changes());
+        if self.unsaved.is_empty() {
+            return Ok(());
+        }
+
+        self.storage
+            .save_changes(self.todo_state.document(), &self.unsaved)?;
+        self.unsaved = ChangeSet::default();
+        Ok(())
+    }
+
+    /// Takes the lock of the open workspace, or makes it read-only if another
+    /// instance holds it.
+    fn lock_workspace(&mut self) {
+        // Switching workspaces releases the previous lock
+        self.lock = None;
+        self.read_only = match WorkspaceLock::acquire(&self.workspace) {
+            Ok(Some(lock)) => {
+                self.lock = Some(lock);
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                // Without a lock file the data file is likely not writable
+                // either, saving reports that
+                log::warn!("Could not lock {}: {}", self.workspace.display(), e);
+                false
+            }
+        };
+        self.todo_state.set_read_only(self.read_only);
+    }
+
+    /// An encrypted workspace starts out locked.
+    fn detect_encryption(&mut self) {
+        self.encryption = if storage::encrypted::is_encrypted(&self.workspace) {
+            Encryption::Locked
+        } else {
+            Encryption::Plain
+        };
+        self.passphrase = PassphraseForm::default();
+    }
+
+    fn is_locked(&self) -> bool {
+        matches!(self.encryption, Encryption::Locked)
+    }
+
+    /// The passphrase to encrypt with, if it is long enough and was repeated
+    /// correctly. Otherwise the form shows why not.
+    fn new_passphrase(&mut self) -> Option<Zeroizing<String>> {
+        let form = &mut self.passphrase;
+        if form.working {
+            return None;
+        }
+        if form.passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+            form.error = Some(format!(
+                "The passphrase needs at least {} characters",
+                MIN_PASSPHRASE_LEN
+            ));
+            return None;
+        }
+        if form.passphrase != form.confirm {
+            form.error = Some(String::from("The passphrases do not match"));
+            return None;
+        }
+        form.confirm = Zeroizing::default();
+        Some(std::mem::take(&mut form.passphrase))
+    }
+
+    /// Saves what is pending, then derives a key and rewrites the files in
+    /// the background. Saving and reloading wait until it is done.
+    fn start_key_derivation(
+        &mut self,
+        derive: impl FnOnce() -> Result<Option<Arc<EncryptedStorage>>, TodoError> + Send + 'static,
+    ) -> Command<Message> {
+        if self.saving || self.reloading {
+            self.passphrase.error = Some(String::from("Still saving, try again in a moment"));
+            return Command::none();
+        }
+        if let Err(error) = self.flush() {
+            self.passphrase.error = Some(error.to_string());
+            return Command::none();
+        }
+        self.passphrase.error = None;
+        self.passphrase.working = true;
+        Command::perform(async move { derive() }, Message::EncryptionChanged)
+    }
+
+    /// The file typed into the CSV panel, if any.
+    fn csv_path(&self) -> Option<PathBuf> {
+        let path = self.todo_state.csv_panel()?.path.trim();
+        (!path.is_empty()).then(|| PathBuf::from(path))
+    }
+
+    /// Writes the todos as CSV, or as todo.txt to a `.txt` file.
+    fn export_csv(&mut self, scope: ExportScope) -> Command<Message> {
+        let Some(path) = self.csv_path() else {
+            return Command::none();
+        };
+        let export = if todotxt::is_todo_txt(&path) {
+            Ok(self.todo_state.export_todo_txt(scope))
+        } else {
+            self.todo_state.export_csv(scope)
+        };
+        match export {
+            Ok((data, count)) => Command::perform(
+                async move {
+                    std::fs::write(&path, data)
+                        .map(|_| count)
+                        .map_err(|e| TodoError::CsvError(format!("{}: {}", path.display(), e)))
+                },
+                Message::CsvExported,
+            ),
+            Err(error) => {
+                self.todo_state.csv_exported(Err(error));
+                Command::none()
+            }
+        }
+    }
+
+    /// Reads a CSV file, or a `.txt` file as todo.txt, for the import wizard.
+    fn read_csv(&mut self) -> Command<Message> {
+        let Some(path) = self.csv_path() else {
+            return Command::none();
+        };
+        Command::perform(
+            async move {
+                let data = std::fs::read_to_string(&path)
+                    .map_err(|e| TodoError::CsvError(format!("{}: {}", path.display(), e)))?;
+                if !todotxt::is_todo_txt(&path) {
+                    return CsvTable::parse(&data);
+                }
+                let document = todotxt::read(&data, TodoDocument::default(), chrono::Local::now());
+                let mut todos: Vec<&Todo> = document.todos.values().collect();
+                todos.sort_by_key(|todo| todo.created_at);
+                Ok(CsvTable::from_todos(&todos, &document.lists))
+            },
+            Message::CsvRead,
+        )
+    }
+
+    fn watched_stamp(&self) -> Option<FileStamp> {
+        self.storage.data_path().and_then(FileStamp::of)
+    }
+
+    /// Whether the data file was replaced since it was last read or written.
+    fn changed_on_disk(&self) -> bool {
+        self.storage.data_path().is_some() && self.watched_stamp() != self.disk_stamp
+    }
+
+    /// Reads the data file again to merge it with the document being edited.
+    fn reload(&mut self) -> Command<Message> {
+        self.reloading = true;
+        // Taken before reading, so a change made meanwhile is noticed again
+        self.disk_stamp = self.watched_stamp();
+        let storage = self.storage.clone();
+        Command::perform(async move { storage.load() }, Message::ExternalChange)
+    }
+
+    /// Merges the document read from disk into the one being edited, with
+    /// the document last synced as the common base.
+    fn merge_external(&mut self, document: TodoDocument) {
+        match &self.synced {
+            Some(base) if *base == document => {}
+            Some(base) => {
+                let pending = std::mem::take(&mut self.unsaved);
+                self.todo_state.merge_external(base, &document, pending);
+                if self.todo_state.has_changes() {
+                    self.last_change = Some(Instant::now());
+                }
+            }
+            None => self.todo_state.set_document(document.clone()),
+        }
+        self.synced = Some(document);
+    }
+}
@@ -0,0 +1,43 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Advisory lock on a data file, held while a window edits it so a second
+/// instance opens the file read-only instead of saving over it.
+#[derive(Debug)]
+pub struct WorkspaceLock {
+    /// Released when closed, which the OS also does if the process crashes
+    _file: File,
+}
+
+impl WorkspaceLock {
+    /// The lock of `todos.json` is `todos.json.lock` next to it.
+    pub fn path(data_path: &Path) -> PathBuf {
+        let mut path = data_path.as_os_str().to_owned();
+        path.push(".lock");
+        PathBuf::from(path)
+    }
+
+    /// Takes the lock of `data_path`, `None` if another instance holds it.
+    pub fn acquire(data_path: &Path) -> io::Result<Option<Self>> {
+        let path = Self::path(data_path);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).ok();
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+
+        // Tells whoever looks at the file which process holds it
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(Some(Self { _file: file }))
+    }
+}
@@ -1,5 +1,5 @@
-use iced::{Application, Command, Element, Settings, Theme, Error};
-use todo_gui::TodoApp;
+use iced::{Application, Settings, Error};
+use todo_gui::{Flags, TodoApp};
 
 // Keep the hide_console function for Windows
 #[cfg(windows)]
@@ -24,13 +24,19 @@ fn main() -> Result<(), Error> {
     #[cfg(windows)]
     hide_console();
     
+    // Warnings about unreadable settings or a failed save go to stderr
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     TodoApp::run(Settings {
+        flags: Flags::parse(std::env::args().skip(1)),
         window: iced::window::Settings {
             size: (800, 600),
             position: iced::window::Position::Centered,
             min_size: Some((400, 300)),
             ..Default::default()
         },
+        // Closing is handled by the app so unsaved changes can be flushed first
+        exit_on_close_request: false,
         ..Default::default()
     })
 } 
\ No newline at end of file
@@ -0,0 +1,133 @@
+use std::collections::BTreeSet;
+
+use crate::storage::ChangeSet;
+use crate::todo::{Todo, TodoDocument, TodoId};
+
+/// Result of merging a document changed outside the app into the one in memory.
+#[derive(Debug, Clone)]
+pub struct Merge {
+    /// Holds the local version of every conflicting todo
+    pub document: TodoDocument,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// A todo changed differently in memory and on disk, `None` where it was removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub id: TodoId,
+    pub ours: Option<Todo>,
+    pub theirs: Option<Todo>,
+}
+
+impl Conflict {
+    pub fn title(&self) -> &str {
+        self.ours
+            .as_ref()
+            .or(self.theirs.as_ref())
+            .map_or("", |todo| todo.title.as_str())
+    }
+}
+
+/// How to settle a conflict.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    KeepMine,
+    UseTheirs,
+}
+
+enum Pick<T> {
+    Take(Option<T>),
+    Conflict,
+}
+
+/// Takes the side that changed since `base`, or either if both made the same change.
+fn pick<T: PartialEq + Clone>(base: Option<&T>, ours: Option<&T>, theirs: Option<&T>) -> Pick<T> {
+    if ours == theirs || theirs == base {
+        Pick::Take(ours.cloned())
+    } else if ours == base {
+        Pick::Take(theirs.cloned())
+    } else {
+        Pick::Conflict
+    }
+}
+
+/// Merges `theirs` into `ours`, both having started from `base`. Changes to
+/// different todos are combined, a todo changed on both sides is a conflict.
+/// Lists and settings changed on both sides keep the local version.
+pub fn three_way(base: &TodoDocument, ours: &TodoDocument, theirs: &TodoDocument) -> Merge {
+    let mut document = ours.clone();
+    let mut conflicts = Vec::new();
+
+    let ids: BTreeSet<TodoId> = base
+        .todos
+        .keys()
+        .chain(ours.todos.keys())
+        .chain(theirs.todos.keys())
+        .copied()
+        .collect();
+    for id in ids {
+        let (b, o, t) = (base.todos.get(&id), ours.todos.get(&id), theirs.todos.get(&id));
+        match pick(b, o, t) {
+            Pick::Take(Some(todo)) => {
+                document.todos.insert(id, todo);
+            }
+            Pick::Take(None) => {
+                document.todos.remove(&id);
+            }
+            Pick::Conflict => conflicts.push(Conflict {
+                id,
+                ours: o.cloned(),
+                theirs: t.cloned(),
+            }),
+        }
+    }
+
+    let list_ids: BTreeSet<usize> = base
+        .lists
+        .keys()
+        .chain(ours.lists.keys())
+        .chain(theirs.lists.keys())
+        .copied()
+        .collect();
+    for id in list_ids {
+        let (b, o, t) = (base.lists.get(&id), ours.lists.get(&id), theirs.lists.get(&id));
+        match pick(b, o, t) {
+            Pick::Take(Some(list)) => {
+                document.lists.insert(id, list);
+            }
+            Pick::Take(None) => {
+                document.lists.remove(&id);
+            }
+            Pick::Conflict => {}
+        }
+    }
+    document.next_list_id = ours.next_list_id.max(theirs.next_list_id);
+
+    if let Pick::Take(Some(settings)) =
+        pick(Some(&base.settings), Some(&ours.settings), Some(&theirs.settings))
+    {
+        document.settings = settings;
+    }
+
+    Merge { document, conflicts }
+}
+
+/// What has to be written to turn the saved `from` into `to`.
+pub fn changes(from: &TodoDocument, to: &TodoDocument) -> ChangeSet {
+    let mut changes = ChangeSet::default();
+    for (id, todo) in &to.todos {
+        if from.todos.get(id) != Some(todo) {
+            changes.todos.insert(*id);
+        }
+    }
+    for id in from.todos.keys() {
+        if !to.todos.contains_key(id) {
+            changes.todos.insert(*id);
+        }
+    }
+    changes.metadata = from.lists != to.lists
+        || from.next_list_id != to.next_list_id
+        || from.settings != to.settings
+        || from.version != to.version;
+    changes
+}
@@ -0,0 +1,158 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::error::{Result, TodoError};
+use crate::todo::TodoId;
+
+/// Version written into the `version` field of `todos.json`.
+pub const CURRENT_VERSION: u32 = 3;
+
+/// Keys of the pre-versioning `TodoState` snapshot that only held UI state.
+const V0_UI_KEYS: [&str; 7] = [
+    "filter",
+    "search_query",
+    "editing",
+    "selected_tags",
+    "tag_match",
+    "selected_list",
+    "sort_order",
+];
+
+/// Detects the layout of a saved file.
+///
+/// * version 0: the whole `TodoState` including UI state, without a `version`
+/// * version 1: the bare `TodoDocument`, without a `version`
+/// * version 2: carries an explicit `version`, todos have numeric ids
+/// * version 3: todos have UUIDs
+pub fn detect_version(value: &Value) -> Result<u32> {
+    match value.get("version") {
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| TodoError::ParseError(format!("invalid version field {}", version))),
+        None if value.get("filter").is_some() || value.get("search_query").is_some() => Ok(0),
+        None => Ok(1),
+    }
+}
+
+/// Upgrades a parsed `todos.json` step by step to `CURRENT_VERSION`.
+pub fn migrate(mut value: Value) -> Result<Value> {
+    let version = detect_version(&value)?;
+    if version > CURRENT_VERSION {
+        return Err(TodoError::UnsupportedVersion {
+            found: version,
+            supported: CURRENT_VERSION,
+        });
+    }
+
+    let root = value
+        .as_object_mut()
+        .ok_or_else(|| TodoError::ParseError(String::from("not a JSON object")))?;
+
+    for from in version..CURRENT_VERSION {
+        match from {
+            0 => v0_to_v1(root),
+            1 => v1_to_v2(root),
+            2 => v2_to_v3(root),
+            _ => unreachable!("no migration from version {}", from),
+        }
+    }
+
+    Ok(value)
+}
+
+/// Drops the UI state, keeping the sort order as a setting.
+fn v0_to_v1(root: &mut Map<String, Value>) {
+    let sort_order = root.get("sort_order").cloned();
+    for key in V0_UI_KEYS {
+        root.remove(key);
+    }
+
+    if let Some(sort_order) = sort_order {
+        let settings = root
+            .entry("settings")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Some(settings) = settings.as_object_mut() {
+            settings.entry("sort_order").or_insert(sort_order);
+        }
+    }
+}
+
+/// Introduces the explicit `version` field.
+fn v1_to_v2(root: &mut Map<String, Value>) {
+    root.insert(String::from("version"), Value::from(2));
+}
+
+/// Replaces the sequential todo ids, which collide between files, with
+/// UUIDs. The `next_id` counter is no longer needed.
+fn v2_to_v3(root: &mut Map<String, Value>) {
+    root.remove("next_id");
+
+    if let Some(Value::Object(todos)) = root.remove("todos") {
+        let mut todos: Vec<(String, Value)> = todos.into_iter().collect();
+        for (key, todo) in &mut todos {
+            if todo.get("id").and_then(Value::as_u64).is_none() {
+                if let (Some(todo), Ok(id)) = (todo.as_object_mut(), key.parse::<u64>()) {
+                    todo.insert(String::from("id"), Value::from(id));
+                }
+            }
+        }
+
+        let ids = derive_ids(todos.iter().map(|(_, todo)| todo));
+        let mut migrated = Map::new();
+        for (key, mut todo) in todos {
+            map_todo_ids(&mut todo, &ids);
+            let key = todo.get("id").and_then(Value::as_str).map_or(key, str::to_string);
+            migrated.insert(key, todo);
+        }
+        root.insert(String::from("todos"), Value::Object(migrated));
+    }
+
+    root.insert(String::from("version"), Value::from(3));
+}
+
+/// UUIDs for the numeric ids of `todos`, derived from the number and the
+/// creation time, so migrating the same file twice gives the same ids while
+/// equal numbers from different files do not collide.
+fn derive_ids<'a>(todos: impl Iterator<Item = &'a Value>) -> HashMap<u64, TodoId> {
+    todos
+        .filter_map(|todo| {
+            let numeric = todo.get("id")?.as_u64()?;
+            let created_at = todo.get("created_at").and_then(Value::as_str).unwrap_or_default();
+            Some((numeric, TodoId::from_key(&format!("{}\n{}", numeric, created_at))))
+        })
+        .collect()
+}
+
+/// Replaces a numeric `id` and `series` of a single todo with UUIDs from
+/// `ids`, so instances of one series stay linked. A series whose first
+/// instance is gone is derived from its number alone.
+fn map_todo_ids(todo: &mut Value, ids: &HashMap<u64, TodoId>) {
+    let Some(todo) = todo.as_object_mut() else {
+        return;
+    };
+    for key in ["id", "series"] {
+        if let Some(numeric) = todo.get(key).and_then(Value::as_u64) {
+            let id = ids
+                .get(&numeric)
+                .copied()
+                .unwrap_or_else(|| TodoId::from_key(&format!("{}\n", numeric)));
+            todo.insert(String::from(key), Value::from(id.to_string()));
+        }
+    }
+}
+
+/// Migrates archived todos, which are stored apart from the document, to
+/// UUIDs. Series shared with todos outside the archive are linked again
+/// only within the archive. Returns whether anything changed.
+pub fn migrate_archived_todos(todos: &mut [Value]) -> bool {
+    let ids = derive_ids(todos.iter());
+    let mut changed = false;
+    for todo in todos {
+        if todo.get("id").is_some_and(Value::is_u64) {
+            map_todo_ids(todo, &ids);
+            changed = true;
+        }
+    }
+    changed
+}
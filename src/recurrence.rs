@@ -0,0 +1,135 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// When a recurring todo comes due again after an instance is completed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recurrence {
+    Daily,
+    /// On each of the given weekdays, or every seven days if none are given
+    Weekly(Vec<Weekday>),
+    /// On the given day of every month, clamped to the length of short months
+    MonthlyOnDay(u32),
+    /// The given number of days after the previous instance was completed
+    AfterCompletion(u32),
+}
+
+impl Recurrence {
+    pub fn weekdays() -> Self {
+        Recurrence::Weekly(vec![
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ])
+    }
+
+    /// The due date of the instance following one due at `due` that was
+    /// completed at `completed_at`. The time of day is kept. Instances
+    /// completed late are followed by the first one due after the day they
+    /// were completed, rather than one that is overdue already.
+    pub fn next_due(&self, due: DateTime<Local>, completed_at: DateTime<Local>) -> DateTime<Local> {
+        let date = due.date_naive();
+        let completed_on = completed_at.date_naive();
+        let next_date = match self {
+            Recurrence::AfterCompletion(days) => completed_on + Duration::days(i64::from(*days).max(1)),
+            _ => {
+                let mut next = self.following(date);
+                while next <= completed_on {
+                    next = self.following(next);
+                }
+                next
+            }
+        };
+
+        next_date
+            .and_time(due.time())
+            .and_local_timezone(Local)
+            .earliest()
+            .unwrap_or(due + (next_date - date))
+    }
+
+    /// The date after `date` this recurs on, ignoring when it was completed.
+    fn following(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Recurrence::Daily => date + Duration::days(1),
+            Recurrence::Weekly(days) if days.is_empty() => date + Duration::days(7),
+            Recurrence::Weekly(days) => (1..=7)
+                .map(|offset| date + Duration::days(offset))
+                .find(|next| days.contains(&next.weekday()))
+                .unwrap_or(date + Duration::days(7)),
+            Recurrence::MonthlyOnDay(day) => next_month_on_day(date, *day),
+            Recurrence::AfterCompletion(days) => date + Duration::days(i64::from(*days).max(1)),
+        }
+    }
+}
+
+/// `day` in the month after `date`'s month, clamped to the last day of that month.
+fn next_month_on_day(date: NaiveDate, day: u32) -> NaiveDate {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+
+    (1..=day.clamp(1, 31))
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap_or(date + Duration::days(30))
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == Self::weekdays() {
+            return write!(f, "Every weekday");
+        }
+
+        match self {
+            Recurrence::Daily => write!(f, "Daily"),
+            Recurrence::Weekly(days) if days.is_empty() => write!(f, "Weekly"),
+            Recurrence::Weekly(days) => {
+                let days: Vec<String> = days.iter().map(ToString::to_string).collect();
+                write!(f, "Weekly on {}", days.join(", "))
+            }
+            Recurrence::MonthlyOnDay(day) => write!(f, "Monthly on day {}", day),
+            Recurrence::AfterCompletion(1) => write!(f, "1 day after completion"),
+            Recurrence::AfterCompletion(days) => write!(f, "{} days after completion", days),
+        }
+    }
+}
+
+/// Reads back what `Display` writes, so recurrences survive a CSV round trip.
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unknown = || format!("unknown recurrence \"{}\"", s);
+        if s.eq_ignore_ascii_case("daily") {
+            return Ok(Recurrence::Daily);
+        }
+        if s.eq_ignore_ascii_case("every weekday") {
+            return Ok(Self::weekdays());
+        }
+        if s.eq_ignore_ascii_case("weekly") {
+            return Ok(Recurrence::Weekly(Vec::new()));
+        }
+        if let Some(days) = s.strip_prefix("Weekly on ") {
+            let days = days
+                .split(',')
+                .map(|day| day.trim().parse::<Weekday>().map_err(|_| unknown()))
+                .collect::<Result<_, _>>()?;
+            return Ok(Recurrence::Weekly(days));
+        }
+        if let Some(day) = s.strip_prefix("Monthly on day ") {
+            return day.parse().map(Recurrence::MonthlyOnDay).map_err(|_| unknown());
+        }
+        let days = s
+            .strip_suffix(" days after completion")
+            .or_else(|| s.strip_suffix(" day after completion"))
+            .ok_or_else(unknown)?;
+        days.parse().map(Recurrence::AfterCompletion).map_err(|_| unknown())
+    }
+}
@@ -0,0 +1,301 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
+
+use crate::error::{Result, TodoError};
+use crate::todo::{Todo, TodoDocument, TodoId};
+use super::json::{self, ArchiveFile, JsonFileStorage};
+use super::Storage;
+
+/// Layout version of the encrypted envelope.
+const ENVELOPE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+
+/// Like `JsonFileStorage`, but the document and its archive are encrypted
+/// with XChaCha20-Poly1305 under a key derived from a passphrase with
+/// Argon2id. Backups are copies of the encrypted file.
+#[derive(Debug, Clone)]
+pub struct EncryptedStorage {
+    path: PathBuf,
+    key: Key,
+}
+
+/// What is written to disk in place of the plain document.
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    /// Envelope version, its presence marks the file as encrypted
+    encrypted: u32,
+    kdf: KdfParams,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// How the key was derived from the passphrase, stored so the parameters
+/// can be raised for new files without breaking old ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct KdfParams {
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+}
+
+#[derive(Clone)]
+struct Key {
+    bytes: Zeroizing<[u8; 32]>,
+    kdf: KdfParams,
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key").field("kdf", &self.kdf).finish_non_exhaustive()
+    }
+}
+
+impl Key {
+    /// Derives a key with a fresh salt and the default parameters.
+    fn generate(passphrase: &str) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(
+            passphrase,
+            KdfParams {
+                memory_kib: Params::DEFAULT_M_COST,
+                iterations: Params::DEFAULT_T_COST,
+                parallelism: Params::DEFAULT_P_COST,
+                salt: BASE64.encode(salt),
+            },
+        )
+    }
+
+    fn derive(passphrase: &str, kdf: KdfParams) -> Result<Self> {
+        let crypto_error = |e: argon2::Error| TodoError::CryptoError(e.to_string());
+        let salt = BASE64
+            .decode(&kdf.salt)
+            .map_err(|e| TodoError::ParseError(format!("salt: {}", e)))?;
+        let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+            .map_err(crypto_error)?;
+
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, bytes.as_mut())
+            .map_err(crypto_error)?;
+        Ok(Self { bytes, kdf })
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        let bytes: &[u8; 32] = &self.bytes;
+        XChaCha20Poly1305::new(bytes.into())
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Result<Envelope> {
+        let cipher = self.cipher();
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|e| TodoError::CryptoError(e.to_string()))?;
+        Ok(Envelope {
+            encrypted: ENVELOPE_VERSION,
+            kdf: self.kdf.clone(),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    /// Fails with `WrongPassphrase` if the key does not authenticate the data.
+    fn open(&self, envelope: &Envelope) -> Result<Zeroizing<Vec<u8>>> {
+        let decode = |field: &str, data: &str| {
+            BASE64
+                .decode(data)
+                .map_err(|e| TodoError::ParseError(format!("{}: {}", field, e)))
+        };
+        let nonce = decode("nonce", &envelope.nonce)?;
+        if nonce.len() != 24 {
+            return Err(TodoError::ParseError(String::from("nonce has the wrong length")));
+        }
+        let ciphertext = decode("ciphertext", &envelope.ciphertext)?;
+
+        self.cipher()
+            .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map(Zeroizing::new)
+            .map_err(|_| TodoError::WrongPassphrase)
+    }
+}
+
+fn read_envelope(path: &Path) -> Result<Envelope> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| TodoError::LoadError(format!("{}: {}", path.display(), e)))?;
+    let envelope: Envelope = serde_json::from_str(&data)
+        .map_err(|e| TodoError::ParseError(format!("{}: {}", path.display(), e)))?;
+    if envelope.encrypted > ENVELOPE_VERSION {
+        return Err(TodoError::UnsupportedVersion {
+            found: envelope.encrypted,
+            supported: ENVELOPE_VERSION,
+        });
+    }
+    Ok(envelope)
+}
+
+/// Whether the file at `path` was written by `EncryptedStorage`.
+pub fn is_encrypted(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+        .is_some_and(|value| value.get("encrypted").is_some())
+}
+
+impl EncryptedStorage {
+    /// Derives the key of the encrypted file at `path` and checks it by
+    /// decrypting the file.
+    pub fn unlock(path: PathBuf, passphrase: &str) -> Result<Self> {
+        let envelope = read_envelope(&path)?;
+        let key = Key::derive(passphrase, envelope.kdf.clone())?;
+        key.open(&envelope)?;
+        Ok(Self { path, key })
+    }
+
+    /// Encrypts the plain JSON document at `path` and its archive.
+    pub fn encrypt(path: PathBuf, passphrase: &str) -> Result<Self> {
+        let plain = JsonFileStorage::new(path.clone());
+        let storage = Self {
+            key: Key::generate(passphrase)?,
+            path,
+        };
+        storage.rewrite(&plain)?;
+        Ok(storage)
+    }
+
+    /// Re-encrypts the document and archive under a new passphrase.
+    pub fn change_passphrase(&self, passphrase: &str) -> Result<Self> {
+        let storage = Self {
+            key: Key::generate(passphrase)?,
+            path: self.path.clone(),
+        };
+        storage.rewrite(self)?;
+        Ok(storage)
+    }
+
+    /// Writes the document and archive back as plain JSON.
+    pub fn decrypt(&self) -> Result<JsonFileStorage> {
+        let document = self.load()?.unwrap_or_default();
+        let archive = ArchiveFile::new(self.load_archive()?);
+
+        // The archive goes first, a plain archive is still read next to an
+        // encrypted document if this is interrupted
+        let plain = JsonFileStorage::new(self.path.clone());
+        plain.replace_archive(&archive)?;
+        plain.save(&document)?;
+        json::remove_backups(&self.path).map_err(|e| TodoError::SaveError(e.to_string()))?;
+        Ok(plain)
+    }
+
+    /// Replaces the document and archive with the ones read by `from`.
+    /// Backups, readable with the old key or without one, are removed, and
+    /// the file being replaced is not backed up first.
+    fn rewrite(&self, from: &dyn Storage) -> Result<()> {
+        let document = from.load()?.unwrap_or_default();
+        let archive = ArchiveFile::new(from.load_archive()?);
+
+        self.write_document(&document)?;
+        self.write_archive_file(&archive)?;
+        json::remove_backups(&self.path).map_err(|e| TodoError::SaveError(e.to_string()))
+    }
+
+    /// Also reads a plain archive, left behind if encrypting was interrupted.
+    fn read_archive(&self) -> Result<ArchiveFile> {
+        let path = json::archive_path(&self.path);
+        if !path.exists() {
+            return Ok(ArchiveFile::new(Vec::new()));
+        }
+
+        let parse_error = |e: serde_json::Error| TodoError::ParseError(format!("{}: {}", path.display(), e));
+        let value: Value = if is_encrypted(&path) {
+            let plaintext = self.key.open(&read_envelope(&path)?)?;
+            serde_json::from_slice(&plaintext).map_err(parse_error)?
+        } else {
+            let data = fs::read_to_string(&path)
+                .map_err(|e| TodoError::LoadError(format!("{}: {}", path.display(), e)))?;
+            serde_json::from_str(&data).map_err(parse_error)?
+        };
+        let (archive, migrated) = ArchiveFile::from_value(value)?;
+        if migrated {
+            self.write_archive_file(&archive)?;
+        }
+        Ok(archive)
+    }
+
+    fn write_document(&self, document: &TodoDocument) -> Result<()> {
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(document)
+                .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?,
+        );
+        self.write_envelope(&self.path, &plaintext)
+    }
+
+    fn write_archive_file(&self, archive: &ArchiveFile) -> Result<()> {
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(archive)
+                .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?,
+        );
+        self.write_envelope(&json::archive_path(&self.path), &plaintext)
+    }
+
+    fn write_envelope(&self, path: &Path, plaintext: &[u8]) -> Result<()> {
+        let envelope = self.key.seal(plaintext)?;
+        let json = serde_json::to_string_pretty(&envelope)
+            .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+        json::write_atomic(path, json.as_bytes()).map_err(|e| TodoError::SaveError(e.to_string()))
+    }
+
+    fn read_document(&self, path: &Path) -> Result<TodoDocument> {
+        let plaintext = self.key.open(&read_envelope(path)?)?;
+        let data = std::str::from_utf8(&plaintext)
+            .map_err(|e| TodoError::ParseError(format!("{}: {}", path.display(), e)))?;
+        TodoDocument::from_json(data)
+    }
+}
+
+impl Storage for EncryptedStorage {
+    fn load(&self) -> Result<Option<TodoDocument>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        self.read_document(&self.path).map(Some)
+    }
+
+    fn save(&self, document: &TodoDocument) -> Result<()> {
+        json::create_backup(&self.path, document.settings.backup_count)
+            .map_err(|e| TodoError::SaveError(format!("backup failed: {}", e)))?;
+        self.write_document(document)
+    }
+
+    fn load_archive(&self) -> Result<Vec<Todo>> {
+        Ok(self.read_archive()?.todos.into_values().collect())
+    }
+
+    fn write_archive(&self, archived: &[Todo], unarchived: &BTreeSet<TodoId>) -> Result<()> {
+        let mut archive = self.read_archive().map_err(|e| TodoError::SaveError(e.to_string()))?;
+        archive.update(archived, unarchived);
+        self.write_archive_file(&archive)
+    }
+
+    fn backups(&self) -> Vec<PathBuf> {
+        json::list_backups(&self.path)
+    }
+
+    fn read_backup(&self, path: &Path) -> Result<TodoDocument> {
+        self.read_document(path)
+    }
+
+    fn data_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
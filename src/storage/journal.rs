@@ -0,0 +1,374 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{Result, TodoError};
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::todo::{Todo, TodoDocument, TodoId};
+use super::json::{self, JsonFileStorage};
+use super::{split, ChangeSet, Storage};
+
+/// Number of journal entries after which the next save compacts them into
+/// the snapshot.
+const COMPACT_AFTER: usize = 200;
+
+/// Key in the snapshot holding the sequence number of the last entry it
+/// contains. Ignored when the snapshot is read as a plain document.
+const SEQ_KEY: &str = "journal_seq";
+
+/// Appends every save as one line to a journal next to a snapshot of the
+/// document, so a save only writes what changed. Loading replays the journal
+/// onto the snapshot and skips a torn last line from a crash, which the next
+/// append cuts off. Loading never writes, so a read-only instance can share
+/// the files.
+///
+/// Entries hold the resulting todos rather than the `todo::Message`s that
+/// changed them: messages read the clock and the input fields when applied,
+/// so replaying them would not give back the same document.
+///
+/// Every save writes the journal, a compaction empties it, so the journal is
+/// the file watched for changes. A snapshot edited by hand is only noticed
+/// on the next start.
+#[derive(Debug)]
+pub struct JournalStorage {
+    snapshot: PathBuf,
+    journal: PathBuf,
+    /// Reuses the JSON backend for the archive file
+    archive: JsonFileStorage,
+    state: Mutex<Option<JournalState>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct JournalState {
+    /// Sequence number of the last entry written
+    seq: u64,
+    /// Entries in the journal since the last compaction
+    entries: usize,
+    /// Layout version of the snapshot, entries must match it
+    version: u32,
+    /// Damage left by a crash, cut off before the next append
+    tail: Option<TornTail>,
+}
+
+/// The end of a journal whose last append was interrupted.
+#[derive(Debug, Clone, Copy)]
+struct TornTail {
+    /// Length of the intact entries
+    len: u64,
+    /// Whether the last intact entry lost only its newline
+    newline: bool,
+}
+
+/// One line of the journal.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    seq: u64,
+    at: DateTime<Local>,
+    /// Todos added or modified, keyed by id
+    #[serde(default)]
+    todos: Map<String, Value>,
+    #[serde(default)]
+    removed: Vec<String>,
+    /// Everything of the document except the todos, if it changed
+    #[serde(default)]
+    metadata: Option<Value>,
+}
+
+impl JournalStorage {
+    /// `snapshot` is the compacted document, the journal is kept next to it
+    /// with the extension `jsonl`.
+    pub fn new(snapshot: PathBuf) -> Self {
+        Self {
+            journal: snapshot.with_extension("jsonl"),
+            archive: JsonFileStorage::new(snapshot.clone()),
+            snapshot,
+            state: Mutex::new(None),
+        }
+    }
+
+    fn read_snapshot(&self) -> Result<Option<Value>> {
+        if !self.snapshot.exists() {
+            return Ok(None);
+        }
+        let data = fs::read_to_string(&self.snapshot)
+            .map_err(|e| TodoError::LoadError(format!("{}: {}", self.snapshot.display(), e)))?;
+        serde_json::from_str(&data)
+            .map(Some)
+            .map_err(|e| TodoError::ParseError(format!("{}: {}", self.snapshot.display(), e)))
+    }
+
+    /// Entries newer than the snapshot, oldest first, and the torn tail to cut
+    /// off before the next entry is appended.
+    fn read_entries(&self, after_seq: u64) -> Result<(Vec<Entry>, Option<TornTail>)> {
+        let data = match fs::read_to_string(&self.journal) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), None)),
+            Err(e) => {
+                return Err(TodoError::LoadError(format!("{}: {}", self.journal.display(), e)))
+            }
+        };
+
+        let lines: Vec<&str> = data.split_inclusive('\n').collect();
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        for (index, line) in lines.iter().enumerate() {
+            let start = offset;
+            offset += line.len();
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Entry>(line) {
+                Ok(entry) if entry.seq > after_seq => entries.push(entry),
+                Ok(_) => {}
+                // A crash while appending leaves the last line incomplete
+                Err(e) if index + 1 == lines.len() => {
+                    log::warn!("Skipping incomplete journal entry: {}", e);
+                    let tail = TornTail {
+                        len: start as u64,
+                        newline: false,
+                    };
+                    return Ok((entries, Some(tail)));
+                }
+                Err(e) => {
+                    return Err(TodoError::ParseError(format!(
+                        "{} line {}: {}",
+                        self.journal.display(),
+                        index + 1,
+                        e
+                    )))
+                }
+            }
+        }
+        // The crash may also have cut off just the newline
+        let tail = (!data.is_empty() && !data.ends_with('\n')).then_some(TornTail {
+            len: data.len() as u64,
+            newline: true,
+        });
+        Ok((entries, tail))
+    }
+
+    /// Truncates the journal to the intact entries and ends it with a newline
+    /// if that was lost.
+    fn repair_tail(&self, tail: TornTail) -> Result<()> {
+        let error = |e: std::io::Error| TodoError::SaveError(format!("{}: {}", self.journal.display(), e));
+        let mut file = OpenOptions::new().write(true).open(&self.journal).map_err(error)?;
+        file.set_len(tail.len).map_err(error)?;
+        if tail.newline {
+            file.seek(SeekFrom::End(0))
+                .and_then(|_| file.write_all(b"\n"))
+                .map_err(error)?;
+        }
+        file.sync_data().map_err(error)
+    }
+
+    /// Reads the snapshot and replays the journal onto it, before migrating
+    /// the result like any other saved document.
+    fn replay(&self) -> Result<(Option<Value>, JournalState)> {
+        let snapshot = self.read_snapshot()?;
+        let snapshot_seq = snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.get(SEQ_KEY))
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        let (entries, tail) = self.read_entries(snapshot_seq)?;
+
+        let version = match &snapshot {
+            Some(snapshot) => migrations::detect_version(snapshot)?,
+            None => CURRENT_VERSION,
+        };
+        let state = JournalState {
+            seq: entries.last().map_or(snapshot_seq, |entry| entry.seq),
+            entries: entries.len(),
+            version,
+            tail,
+        };
+
+        if snapshot.is_none() && entries.is_empty() {
+            return Ok((None, state));
+        }
+
+        let mut value = match snapshot {
+            Some(snapshot) => snapshot,
+            None => serde_json::to_value(TodoDocument::default())
+                .map_err(|e| TodoError::ParseError(e.to_string()))?,
+        };
+        let root = value
+            .as_object_mut()
+            .ok_or_else(|| TodoError::ParseError(String::from("snapshot is not a JSON object")))?;
+        root.remove(SEQ_KEY);
+
+        for entry in entries {
+            if let Some(Value::Object(metadata)) = entry.metadata {
+                let todos = root.remove("todos");
+                root.clear();
+                root.extend(metadata);
+                if let Some(todos) = todos {
+                    root.insert(String::from("todos"), todos);
+                }
+            }
+            let todos = root
+                .entry("todos")
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Some(todos) = todos.as_object_mut() {
+                for id in entry.removed {
+                    todos.remove(&id);
+                }
+                todos.extend(entry.todos);
+            }
+        }
+
+        Ok((Some(value), state))
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut JournalState) -> Result<T>) -> Result<T> {
+        let mut guard = self
+            .state
+            .lock()
+            .map_err(|_| TodoError::SaveError(String::from("journal state poisoned")))?;
+        let state = match guard.as_mut() {
+            Some(state) => state,
+            None => guard.insert(
+                self.replay()
+                    .map_err(|e| TodoError::SaveError(e.to_string()))?
+                    .1,
+            ),
+        };
+        f(state)
+    }
+
+    /// Writes the whole document as the new snapshot and empties the journal.
+    /// The journal is truncated rather than removed, so watching it notices
+    /// the compaction.
+    fn compact(&self, document: &TodoDocument, state: &mut JournalState) -> Result<()> {
+        let mut value = serde_json::to_value(document)
+            .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+        if let Some(root) = value.as_object_mut() {
+            root.insert(String::from(SEQ_KEY), Value::from(state.seq));
+        }
+        let json = serde_json::to_string_pretty(&value)
+            .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+
+        json::create_backup(&self.snapshot, document.settings.backup_count)
+            .map_err(|e| TodoError::SaveError(format!("backup failed: {}", e)))?;
+        json::write_atomic(&self.snapshot, json.as_bytes())
+            .map_err(|e| TodoError::SaveError(e.to_string()))?;
+
+        // Entries left behind by a crash right here are older than the
+        // snapshot's sequence number and skipped on load
+        fs::File::create(&self.journal)
+            .map_err(|e| TodoError::SaveError(format!("{}: {}", self.journal.display(), e)))?;
+        state.entries = 0;
+        state.tail = None;
+        state.version = CURRENT_VERSION;
+        Ok(())
+    }
+
+    fn append(
+        &self,
+        document: &TodoDocument,
+        changes: &ChangeSet,
+        state: &mut JournalState,
+    ) -> Result<()> {
+        let metadata = if changes.metadata {
+            Some(split(document)?.0)
+        } else {
+            None
+        };
+        let mut entry = Entry {
+            seq: state.seq + 1,
+            at: Local::now(),
+            todos: Map::new(),
+            removed: Vec::new(),
+            metadata,
+        };
+        for id in &changes.todos {
+            match document.todos.get(id) {
+                Some(todo) => {
+                    let todo = serde_json::to_value(todo)
+                        .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+                    entry.todos.insert(id.to_string(), todo);
+                }
+                None => entry.removed.push(id.to_string()),
+            }
+        }
+
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+        line.push('\n');
+
+        if let Some(tail) = state.tail {
+            self.repair_tail(tail)?;
+            state.tail = None;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.journal)
+            .map_err(|e| TodoError::SaveError(format!("{}: {}", self.journal.display(), e)))?;
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.sync_data())
+            .map_err(|e| TodoError::SaveError(e.to_string()))?;
+
+        state.seq = entry.seq;
+        state.entries += 1;
+        Ok(())
+    }
+}
+
+impl Storage for JournalStorage {
+    fn load(&self) -> Result<Option<TodoDocument>> {
+        let (value, state) = self.replay()?;
+        if let Ok(mut guard) = self.state.lock() {
+            *guard = Some(state);
+        }
+        value.map(TodoDocument::from_value).transpose()
+    }
+
+    fn save(&self, document: &TodoDocument) -> Result<()> {
+        self.with_state(|state| self.compact(document, state))
+    }
+
+    /// Appends the changes, or compacts when the journal has grown long, the
+    /// whole document changed or the snapshot is from an older version.
+    fn save_changes(&self, document: &TodoDocument, changes: &ChangeSet) -> Result<()> {
+        if !changes.archived.is_empty() {
+            self.archive.write_archive(&changes.archived, &BTreeSet::new())?;
+        }
+        self.with_state(|state| {
+            let compact = changes.full
+                || state.entries >= COMPACT_AFTER
+                || state.version != CURRENT_VERSION
+                || !self.snapshot.exists();
+            if compact {
+                self.compact(document, state)
+            } else {
+                self.append(document, changes, state)
+            }
+        })?;
+        if !changes.unarchived.is_empty() {
+            self.archive.write_archive(&[], &changes.unarchived)?;
+        }
+        Ok(())
+    }
+
+    fn load_archive(&self) -> Result<Vec<Todo>> {
+        self.archive.load_archive()
+    }
+
+    fn write_archive(&self, archived: &[Todo], unarchived: &BTreeSet<TodoId>) -> Result<()> {
+        self.archive.write_archive(archived, unarchived)
+    }
+
+    fn backups(&self) -> Vec<PathBuf> {
+        json::list_backups(&self.snapshot)
+    }
+
+    fn data_path(&self) -> Option<&Path> {
+        Some(&self.journal)
+    }
+}
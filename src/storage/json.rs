@@ -0,0 +1,271 @@
+use chrono::{Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::{Result, TodoError};
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::todo::{Todo, TodoDocument, TodoId};
+use super::{parse_archived, Storage};
+
+/// Minimum age of the newest backup before another one is taken, so saving on
+/// every change does not flood the backup directory.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+const BACKUP_TIMESTAMP: &str = "%Y%m%d-%H%M%S";
+const BACKUP_TIMESTAMP_LEN: usize = "20240101-120000".len();
+
+/// Stores the whole document in a single pretty-printed JSON file, rewritten
+/// atomically on every save with rotating backups next to it.
+#[derive(Debug, Clone)]
+pub struct JsonFileStorage {
+    path: PathBuf,
+}
+
+impl JsonFileStorage {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read_archive(&self) -> Result<ArchiveFile> {
+        let path = archive_path(&self.path);
+        if !path.exists() {
+            return Ok(ArchiveFile::default());
+        }
+
+        let data = fs::read_to_string(&path)
+            .map_err(|e| TodoError::LoadError(format!("{}: {}", path.display(), e)))?;
+        let value: Value = serde_json::from_str(&data)
+            .map_err(|e| TodoError::ParseError(format!("{}: {}", path.display(), e)))?;
+        let (archive, migrated) = ArchiveFile::from_value(value)?;
+        if migrated {
+            // Migration gives todos without an id new ones, keep them
+            self.replace_archive(&archive)?;
+        }
+        Ok(archive)
+    }
+
+    /// Writes `archive` as a whole, replacing whatever was there.
+    pub(super) fn replace_archive(&self, archive: &ArchiveFile) -> Result<()> {
+        let json = serde_json::to_string_pretty(archive)
+            .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+        write_atomic(&archive_path(&self.path), json.as_bytes())
+            .map_err(|e| TodoError::SaveError(e.to_string()))
+    }
+}
+
+/// `todos.json` keeps its archive in `todos-archive.json` next to it.
+pub(super) fn archive_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("todos");
+    path.with_file_name(format!("{}-archive.json", stem))
+}
+
+/// Contents of the archive file.
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct ArchiveFile {
+    pub(super) version: u32,
+    pub(super) todos: BTreeMap<TodoId, Todo>,
+}
+
+impl ArchiveFile {
+    pub(super) fn update(&mut self, archived: &[Todo], unarchived: &BTreeSet<TodoId>) {
+        self.version = CURRENT_VERSION;
+        for todo in archived {
+            self.todos.insert(todo.id, todo.clone());
+        }
+        self.todos.retain(|id, _| !unarchived.contains(id));
+    }
+
+    pub(super) fn new(todos: Vec<Todo>) -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            todos: todos.into_iter().map(|todo| (todo.id, todo)).collect(),
+        }
+    }
+
+    /// Parses an archive of any known version, migrating its todos. Also
+    /// returns whether they were migrated and should be written back.
+    pub(super) fn from_value(value: Value) -> Result<(Self, bool)> {
+        let version = migrations::detect_version(&value)?;
+        if version > CURRENT_VERSION {
+            return Err(TodoError::UnsupportedVersion {
+                found: version,
+                supported: CURRENT_VERSION,
+            });
+        }
+
+        let todos = match value.get("todos") {
+            Some(Value::Object(todos)) => todos.values().cloned().collect(),
+            _ => Vec::new(),
+        };
+        let (todos, migrated) = parse_archived(todos)?;
+        let archive = Self {
+            version: if migrated { CURRENT_VERSION } else { version },
+            todos: todos.into_iter().map(|todo| (todo.id, todo)).collect(),
+        };
+        Ok((archive, migrated))
+    }
+}
+
+impl Default for ArchiveFile {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            todos: BTreeMap::new(),
+        }
+    }
+}
+
+impl Storage for JsonFileStorage {
+    fn load(&self) -> Result<Option<TodoDocument>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+
+        read_document(&self.path).map(Some)
+    }
+
+    fn save(&self, document: &TodoDocument) -> Result<()> {
+        let json = serde_json::to_string_pretty(document)
+            .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+
+        create_backup(&self.path, document.settings.backup_count)
+            .map_err(|e| TodoError::SaveError(format!("backup failed: {}", e)))?;
+        write_atomic(&self.path, json.as_bytes())
+            .map_err(|e| TodoError::SaveError(e.to_string()))
+    }
+
+    fn load_archive(&self) -> Result<Vec<Todo>> {
+        Ok(self.read_archive()?.todos.into_values().collect())
+    }
+
+    fn write_archive(&self, archived: &[Todo], unarchived: &BTreeSet<TodoId>) -> Result<()> {
+        let mut archive = self.read_archive().map_err(|e| TodoError::SaveError(e.to_string()))?;
+        archive.update(archived, unarchived);
+        self.replace_archive(&archive)
+    }
+
+    fn backups(&self) -> Vec<PathBuf> {
+        list_backups(&self.path)
+    }
+
+    fn data_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+/// Reads a JSON document from any file, e.g. one of the backups.
+pub fn read_document(path: &Path) -> Result<TodoDocument> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| TodoError::LoadError(format!("{}: {}", path.display(), e)))?;
+
+    TodoDocument::from_json(&data)
+}
+
+/// Replaces `path` with `contents` so that readers see either the old or the
+/// new file, never a partially written one.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&temp_path, path)?;
+
+    // Persist the rename itself
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.parent()
+        .map_or_else(|| PathBuf::from("backups"), |dir| dir.join("backups"))
+}
+
+/// Backups of `todos.json` are named `todos-<timestamp>.json`.
+fn backup_prefix(path: &Path) -> String {
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("todos");
+    format!("{}-", stem)
+}
+
+/// Backups of `path`, newest first.
+pub fn list_backups(path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(backup_dir(path)) else {
+        return Vec::new();
+    };
+
+    let prefix = backup_prefix(path);
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|backup| {
+            backup
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix(&prefix))
+                .is_some_and(|rest| rest.len() == BACKUP_TIMESTAMP_LEN)
+        })
+        .filter(|backup| backup_time(backup).is_some())
+        .collect();
+    backups.sort_by_key(|path| std::cmp::Reverse(backup_time(path)));
+    backups
+}
+
+/// When a backup was taken, parsed from its file name.
+pub fn backup_time(path: &Path) -> Option<NaiveDateTime> {
+    let stem = path.file_stem()?.to_str()?;
+    let timestamp = stem.get(stem.len().checked_sub(BACKUP_TIMESTAMP_LEN)?..)?;
+    NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP).ok()
+}
+
+/// Deletes every backup of `path`.
+pub fn remove_backups(path: &Path) -> io::Result<()> {
+    for backup in list_backups(path) {
+        fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+/// Copies the current `path` into the backup directory unless the newest backup
+/// is recent, then deletes all but the `keep` newest backups.
+pub fn create_backup(path: &Path, keep: usize) -> io::Result<()> {
+    if !path.exists() || keep == 0 {
+        return Ok(());
+    }
+
+    let now = Local::now().naive_local();
+    let backups = list_backups(path);
+    let is_recent = backups
+        .first()
+        .and_then(|newest| backup_time(newest))
+        .and_then(|taken| (now - taken).to_std().ok())
+        .is_some_and(|age| age < BACKUP_INTERVAL);
+    if is_recent {
+        return Ok(());
+    }
+
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir)?;
+    let mut name = format!("{}{}", backup_prefix(path), now.format(BACKUP_TIMESTAMP));
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        name = format!("{}.{}", name, extension);
+    }
+    fs::copy(path, dir.join(name))?;
+
+    for old in list_backups(path).iter().skip(keep) {
+        fs::remove_file(old)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::error::{Result, TodoError};
+use crate::migrations;
+use crate::todo::{Todo, TodoDocument, TodoId};
+
+pub mod encrypted;
+pub mod journal;
+pub mod json;
+pub mod sqlite;
+pub mod todotxt;
+
+pub use encrypted::EncryptedStorage;
+pub use journal::JournalStorage;
+pub use json::JsonFileStorage;
+pub use sqlite::SqliteStorage;
+pub use todotxt::TodoTxtStorage;
+
+/// Where the document is persisted.
+pub trait Storage: fmt::Debug + Send + Sync {
+    /// Loads the saved document, `None` if nothing has been saved yet.
+    fn load(&self) -> Result<Option<TodoDocument>>;
+
+    /// Writes the whole document.
+    fn save(&self, document: &TodoDocument) -> Result<()>;
+
+    /// Writes only what `changes` touched: todos in `changes.todos` are
+    /// upserted if they are still in `document` and deleted otherwise.
+    /// Backends without incremental writes rewrite everything.
+    ///
+    /// Archived todos are written before the document no longer holds them
+    /// and restored ones are removed from the archive only after the document
+    /// holds them again, so an interrupted save never loses a todo.
+    fn save_changes(&self, document: &TodoDocument, changes: &ChangeSet) -> Result<()> {
+        if !changes.archived.is_empty() {
+            self.write_archive(&changes.archived, &BTreeSet::new())?;
+        }
+        self.save(document)?;
+        if !changes.unarchived.is_empty() {
+            self.write_archive(&[], &changes.unarchived)?;
+        }
+        Ok(())
+    }
+
+    /// Loads every archived todo, kept apart from the document so the todos
+    /// loaded on startup stay few.
+    fn load_archive(&self) -> Result<Vec<Todo>>;
+
+    /// Adds `archived` to the archive and removes the todos in `unarchived`.
+    fn write_archive(&self, archived: &[Todo], unarchived: &BTreeSet<TodoId>) -> Result<()>;
+
+    /// Backups that can be restored with `read_backup`, newest first.
+    fn backups(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    fn read_backup(&self, path: &Path) -> Result<TodoDocument> {
+        json::read_document(path)
+    }
+
+    /// The file to watch for changes made outside the app, `None` for
+    /// backends whose files are not meant to be edited by hand.
+    fn data_path(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Identifies one version of a file on disk, to notice when it was replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileStamp {
+    /// `None` if the file does not exist.
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+/// What changed in a document since it was last saved.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChangeSet {
+    /// Todos that were added, modified or removed
+    pub todos: BTreeSet<TodoId>,
+    /// Anything besides the todos changed: lists, counters or settings
+    pub metadata: bool,
+    /// The document was replaced as a whole and must be rewritten completely
+    pub full: bool,
+    /// Todos moved out of the document into the archive
+    pub archived: Vec<Todo>,
+    /// Todos taken out of the archive back into the document
+    pub unarchived: BTreeSet<TodoId>,
+}
+
+impl ChangeSet {
+    pub fn full() -> Self {
+        Self {
+            full: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.todos.is_empty()
+            && !self.metadata
+            && !self.full
+            && self.archived.is_empty()
+            && self.unarchived.is_empty()
+    }
+
+    pub fn merge(&mut self, other: ChangeSet) {
+        self.todos.extend(other.todos);
+        self.metadata |= other.metadata;
+        self.full |= other.full;
+        for todo in other.archived {
+            self.archive(todo);
+        }
+        for id in other.unarchived {
+            self.unarchive(id);
+        }
+    }
+
+    /// Records a todo moved into the archive, superseding an earlier restore.
+    pub fn archive(&mut self, todo: Todo) {
+        self.todos.insert(todo.id);
+        self.unarchived.remove(&todo.id);
+        self.archived.retain(|archived| archived.id != todo.id);
+        self.archived.push(todo);
+    }
+
+    /// Records a todo restored from the archive, superseding an earlier archive.
+    pub fn unarchive(&mut self, id: TodoId) {
+        self.todos.insert(id);
+        self.archived.retain(|archived| archived.id != id);
+        self.unarchived.insert(id);
+    }
+}
+
+/// Splits a document into its metadata and one JSON value per todo id, for
+/// backends that write todos individually.
+fn split(document: &TodoDocument) -> Result<(Value, Map<String, Value>)> {
+    let mut value = serde_json::to_value(document)
+        .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+    let todos = match value.as_object_mut().and_then(|root| root.remove("todos")) {
+        Some(Value::Object(todos)) => todos,
+        _ => Map::new(),
+    };
+    Ok((value, todos))
+}
+
+/// Deserializes archived todos, migrating ones saved with numeric ids.
+/// Also returns whether any needed migrating, so the backend can rewrite them.
+fn parse_archived(mut todos: Vec<Value>) -> Result<(Vec<Todo>, bool)> {
+    let migrated = migrations::migrate_archived_todos(&mut todos);
+    let todos = todos
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| TodoError::ParseError(format!("archived todo: {}", e)))?;
+    Ok((todos, migrated))
+}
+
+/// Which `Storage` implementation to use, chosen in the config file or by
+/// the extension of a data file opened directly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    /// `todos.json`
+    #[default]
+    Json,
+    /// `todos.db`
+    Sqlite,
+    /// `journal/todos.json` with the changes since in `journal/todos.jsonl`
+    Journal,
+    /// `todo.txt`, with what the format cannot hold in `todo.meta.json`
+    TodoTxt,
+}
+
+impl StorageKind {
+    /// `.db` and `.sqlite` files are SQLite databases, `.jsonl` files journals,
+    /// `.txt` files todo.txt and anything else a JSON document.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("db" | "sqlite" | "sqlite3") => StorageKind::Sqlite,
+            Some("jsonl") => StorageKind::Journal,
+            Some("txt") => StorageKind::TodoTxt,
+            _ => StorageKind::Json,
+        }
+    }
+}
+
+/// Directory holding the data files, created on first use.
+pub fn data_dir() -> PathBuf {
+    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+    path.push("todo_gui");
+    fs::create_dir_all(&path).ok();
+    path
+}
+
+/// The data file used when no other one was chosen.
+pub fn default_path(kind: StorageKind) -> PathBuf {
+    match kind {
+        StorageKind::Json => data_dir().join("todos.json"),
+        StorageKind::Sqlite => data_dir().join("todos.db"),
+        StorageKind::Journal => data_dir().join("journal").join("todos.jsonl"),
+        StorageKind::TodoTxt => data_dir().join("todo.txt"),
+    }
+}
+
+/// Opens the data file at `path` with the backend matching its extension.
+/// A journal is given by its `.jsonl` file, the snapshot is kept next to it.
+pub fn open(path: &Path) -> Arc<dyn Storage> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).ok();
+    }
+    match StorageKind::for_path(path) {
+        StorageKind::Json => Arc::new(JsonFileStorage::new(path.to_path_buf())),
+        StorageKind::Sqlite => Arc::new(SqliteStorage::new(path.to_path_buf())),
+        StorageKind::Journal => Arc::new(JournalStorage::new(path.with_extension("json"))),
+        StorageKind::TodoTxt => Arc::new(TodoTxtStorage::new(path.to_path_buf())),
+    }
+}
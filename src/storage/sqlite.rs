@@ -0,0 +1,240 @@
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::error::{Result, TodoError};
+use crate::todo::{Todo, TodoDocument, TodoId};
+use super::{parse_archived, split, ChangeSet, Storage};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS metadata (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS todos (id TEXT PRIMARY KEY, data TEXT NOT NULL);
+    CREATE TABLE IF NOT EXISTS archive (id TEXT PRIMARY KEY, data TEXT NOT NULL);
+";
+
+/// Key of the metadata row holding everything of the document except the todos.
+const DOCUMENT_KEY: &str = "document";
+
+/// Stores each todo as its own row so saving a change only touches that row.
+/// Rows hold the same JSON as `todos.json`, so the document migrations apply.
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let connection = Connection::open(&self.path).map_err(|e| {
+            TodoError::LoadError(format!("{}: {}", self.path.display(), e))
+        })?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| TodoError::LoadError(e.to_string()))?;
+        Ok(connection)
+    }
+
+    fn has_metadata(&self) -> Result<bool> {
+        self.connect()?
+            .query_row(
+                "SELECT 1 FROM metadata WHERE key = ?1",
+                params![DOCUMENT_KEY],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(|e| TodoError::LoadError(e.to_string()))
+    }
+
+    fn write(&self, write: impl FnOnce(&Transaction) -> rusqlite::Result<()>) -> Result<()> {
+        let mut connection = self.connect().map_err(|e| TodoError::SaveError(e.to_string()))?;
+        let transaction = connection
+            .transaction()
+            .map_err(|e| TodoError::SaveError(e.to_string()))?;
+
+        write(&transaction)
+            .and_then(|()| transaction.commit())
+            .map_err(|e| TodoError::SaveError(e.to_string()))
+    }
+}
+
+fn write_metadata(transaction: &Transaction, metadata: &Value) -> rusqlite::Result<()> {
+    transaction.execute(
+        "INSERT OR REPLACE INTO metadata (key, value) VALUES (?1, ?2)",
+        params![DOCUMENT_KEY, metadata.to_string()],
+    )?;
+    Ok(())
+}
+
+fn upsert_todo(transaction: &Transaction, id: &str, todo: &Value) -> rusqlite::Result<()> {
+    transaction.execute(
+        "INSERT OR REPLACE INTO todos (id, data) VALUES (?1, ?2)",
+        params![id, todo.to_string()],
+    )?;
+    Ok(())
+}
+
+fn delete_todo(transaction: &Transaction, id: &str) -> rusqlite::Result<()> {
+    transaction.execute("DELETE FROM todos WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+/// Serializes the todos moved into the archive, keyed like the `todos` rows.
+fn archive_rows(archived: &[Todo]) -> Result<Vec<(String, String)>> {
+    archived
+        .iter()
+        .map(|todo| {
+            serde_json::to_string(todo)
+                .map(|data| (todo.id.to_string(), data))
+                .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))
+        })
+        .collect()
+}
+
+fn update_archive(
+    transaction: &Transaction,
+    archived: &[(String, String)],
+    unarchived: &BTreeSet<TodoId>,
+) -> rusqlite::Result<()> {
+    for (id, data) in archived {
+        transaction.execute(
+            "INSERT OR REPLACE INTO archive (id, data) VALUES (?1, ?2)",
+            params![id, data],
+        )?;
+    }
+    for id in unarchived {
+        transaction.execute("DELETE FROM archive WHERE id = ?1", params![id.to_string()])?;
+    }
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Option<TodoDocument>> {
+        let connection = self.connect()?;
+        let load_error = |e: rusqlite::Error| TodoError::LoadError(e.to_string());
+        let parse_error = |e: serde_json::Error| TodoError::ParseError(e.to_string());
+
+        let metadata: Option<String> = connection
+            .query_row(
+                "SELECT value FROM metadata WHERE key = ?1",
+                params![DOCUMENT_KEY],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(load_error)?;
+        let Some(metadata) = metadata else {
+            return Ok(None);
+        };
+        let mut value: Value = serde_json::from_str(&metadata).map_err(parse_error)?;
+
+        let mut statement = connection
+            .prepare("SELECT id, data FROM todos")
+            .map_err(load_error)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(load_error)?;
+
+        let mut todos = Map::new();
+        for row in rows {
+            let (id, data) = row.map_err(load_error)?;
+            todos.insert(id, serde_json::from_str(&data).map_err(parse_error)?);
+        }
+
+        let root = value
+            .as_object_mut()
+            .ok_or_else(|| TodoError::ParseError(String::from("metadata is not a JSON object")))?;
+        root.insert(String::from("todos"), Value::Object(todos));
+
+        TodoDocument::from_value(value).map(Some)
+    }
+
+    fn load_archive(&self) -> Result<Vec<Todo>> {
+        let connection = self.connect()?;
+        let load_error = |e: rusqlite::Error| TodoError::LoadError(e.to_string());
+
+        let mut statement = connection
+            .prepare("SELECT data FROM archive")
+            .map_err(load_error)?;
+        let rows = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(load_error)?;
+
+        let mut values = Vec::new();
+        for row in rows {
+            let data = row.map_err(load_error)?;
+            values.push(serde_json::from_str(&data).map_err(|e| TodoError::ParseError(e.to_string()))?);
+        }
+
+        let (todos, migrated) = parse_archived(values)?;
+        if migrated {
+            // Rows are keyed by id, rekey them by the new UUIDs
+            let rows = archive_rows(&todos)?;
+            self.write(|transaction| {
+                transaction.execute("DELETE FROM archive", [])?;
+                update_archive(transaction, &rows, &BTreeSet::new())
+            })?;
+        }
+        Ok(todos)
+    }
+
+    fn write_archive(&self, archived: &[Todo], unarchived: &BTreeSet<TodoId>) -> Result<()> {
+        let archived = archive_rows(archived)?;
+        self.write(|transaction| update_archive(transaction, &archived, unarchived))
+    }
+
+    fn save(&self, document: &TodoDocument) -> Result<()> {
+        self.save_changes(document, &ChangeSet::full())
+    }
+
+    /// Archive changes are written in the same transaction as the document.
+    fn save_changes(&self, document: &TodoDocument, changes: &ChangeSet) -> Result<()> {
+        let archived = archive_rows(&changes.archived)?;
+        if changes.full {
+            let (metadata, todos) = split(document)?;
+            return self.write(|transaction| {
+                update_archive(transaction, &archived, &changes.unarchived)?;
+                transaction.execute("DELETE FROM todos", [])?;
+                for (id, todo) in &todos {
+                    upsert_todo(transaction, id, todo)?;
+                }
+                write_metadata(transaction, &metadata)
+            });
+        }
+
+        // Without the metadata row the todos would not load, so the first
+        // incremental save of a new database writes it as well
+        let metadata = if changes.metadata || !self.has_metadata()? {
+            Some(split(document)?.0)
+        } else {
+            None
+        };
+        let mut todos = Vec::new();
+        for id in &changes.todos {
+            let todo = document
+                .todos
+                .get(id)
+                .map(serde_json::to_value)
+                .transpose()
+                .map_err(|e| TodoError::SaveError(format!("serialization failed: {}", e)))?;
+            todos.push((id.to_string(), todo));
+        }
+
+        self.write(|transaction| {
+            update_archive(transaction, &archived, &changes.unarchived)?;
+            for (id, todo) in &todos {
+                match todo {
+                    Some(todo) => upsert_todo(transaction, id, todo)?,
+                    None => delete_todo(transaction, id)?,
+                }
+            }
+            match &metadata {
+                Some(metadata) => write_metadata(transaction, metadata),
+                None => Ok(()),
+            }
+        })
+    }
+}
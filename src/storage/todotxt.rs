@@ -0,0 +1,73 @@
+use chrono::Local;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, TodoError};
+use crate::todo::{Todo, TodoDocument, TodoId};
+use crate::todotxt;
+use super::json::{self, JsonFileStorage};
+use super::Storage;
+
+/// Keeps the todos in a todo.txt file that other todo.txt tools can edit.
+/// What the format cannot hold, like descriptions, subtasks, recurrence, the
+/// trash and the settings, is kept in a JSON document next to it
+/// (`todo.meta.json` for `todo.txt`), matched to the lines by their `id:`.
+/// The archive is kept next to that document.
+#[derive(Debug, Clone)]
+pub struct TodoTxtStorage {
+    path: PathBuf,
+    meta: JsonFileStorage,
+}
+
+impl TodoTxtStorage {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            meta: JsonFileStorage::new(meta_path(&path)),
+            path,
+        }
+    }
+}
+
+/// The JSON document kept next to a todo.txt file.
+pub fn meta_path(path: &Path) -> PathBuf {
+    path.with_extension("meta.json")
+}
+
+impl Storage for TodoTxtStorage {
+    /// The file is the source of truth: a missing file means nothing was
+    /// saved yet, even if the JSON document is still there.
+    fn load(&self) -> Result<Option<TodoDocument>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read_to_string(&self.path)
+            .map_err(|e| TodoError::LoadError(format!("{}: {}", self.path.display(), e)))?;
+        let base = self.meta.load()?.unwrap_or_default();
+        Ok(Some(todotxt::read(&data, base, Local::now())))
+    }
+
+    /// The todo.txt file goes first. If saving is interrupted before the
+    /// JSON document is written, new lines are still read as new todos.
+    fn save(&self, document: &TodoDocument) -> Result<()> {
+        json::write_atomic(&self.path, todotxt::write(document).as_bytes())
+            .map_err(|e| TodoError::SaveError(e.to_string()))?;
+        self.meta.save(document)
+    }
+
+    fn load_archive(&self) -> Result<Vec<Todo>> {
+        self.meta.load_archive()
+    }
+
+    fn write_archive(&self, archived: &[Todo], unarchived: &BTreeSet<TodoId>) -> Result<()> {
+        self.meta.write_archive(archived, unarchived)
+    }
+
+    fn backups(&self) -> Vec<PathBuf> {
+        self.meta.backups()
+    }
+
+    fn data_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
@@ -1,25 +1,380 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use crate::activity::{self, ActivityEvent, Change};
+use crate::csv_io::{self, CsvPanel, CsvTable, DuplicateAction, ExportScope, Field, ImportRow, ImportWizard};
+use crate::error::{Result, TodoError};
+use crate::history::History;
+use crate::merge::{self, Conflict, Resolution};
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::recurrence::Recurrence;
+use crate::storage::ChangeSet;
+use crate::todotxt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration as StdDuration, Instant};
+use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Globally unique id of a todo, so todos from different devices or files
+/// never collide when they are merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TodoId(Uuid);
+
+/// Namespace of the ids `TodoId::from_key` derives. Changing it changes
+/// every derived id.
+const KEY_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_2a7e_93d4_4b0e_8a55_c2e1_7d3f_90b4);
+
+impl TodoId {
+    pub fn random() -> Self {
+        Self(Uuid::new_v4())
+    }
+
+    /// The same name-based (v5) id every time it is made from `key`, for
+    /// todos read from files that do not store ids.
+    pub fn from_key(key: &str) -> Self {
+        Self(Uuid::new_v5(&KEY_NAMESPACE, key.as_bytes()))
+    }
+}
+
+impl std::fmt::Display for TodoId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for TodoId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(Self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Todo {
-    pub id: usize,
+    pub id: TodoId,
     pub title: String,
     pub description: String,
     pub completed: bool,
     pub created_at: DateTime<Local>,
+    /// When the todo was last completed, `None` while it is open and for
+    /// todos completed before this was recorded.
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Local>>,
+    /// When the todo was last changed, `None` for todos not changed since
+    /// this was recorded.
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub due: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub subtasks: Vec<Subtask>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    #[serde(default = "inbox_list_id")]
+    pub list_id: usize,
+    /// Id of the first instance of a recurring todo, shared by every instance
+    /// spawned from it so completed instances stay linked as history.
+    #[serde(default)]
+    pub series: Option<TodoId>,
+    /// When the todo was moved to the trash, `None` for live todos.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Local>>,
+    /// Every change made to the todo, oldest first
+    #[serde(default)]
+    pub activity: Vec<ActivityEvent>,
+}
+
+/// Id of the built-in list that cannot be archived or deleted.
+pub const INBOX_LIST_ID: usize = 1;
+
+fn inbox_list_id() -> usize {
+    INBOX_LIST_ID
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TodoList {
+    pub id: usize,
+    pub name: String,
+    #[serde(default)]
+    pub archived: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_lists() -> BTreeMap<usize, TodoList> {
+    BTreeMap::from([(
+        INBOX_LIST_ID,
+        TodoList {
+            id: INBOX_LIST_ID,
+            name: String::from("Inbox"),
+            archived: false,
+        },
+    )])
+}
+
+fn default_next_list_id() -> usize {
+    INBOX_LIST_ID + 1
+}
+
+/// A checklist item nested under a `Todo`. Ids are only unique within the parent.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subtask {
+    pub id: usize,
+    pub title: String,
+    pub completed: bool,
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 5] = [
+        Priority::None,
+        Priority::Low,
+        Priority::Medium,
+        Priority::High,
+        Priority::Urgent,
+    ];
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Priority::None => "No priority",
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+            Priority::Urgent => "Urgent",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DueStatus {
+    Overdue,
+    DueToday,
+    Upcoming,
+}
+
+impl Todo {
+    pub fn is_trashed(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// When the todo was last changed, falling back to its creation.
+    pub fn last_updated(&self) -> DateTime<Local> {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+
+    /// How the due date relates to `now`. Completed todos are never overdue.
+    pub fn due_status(&self, now: DateTime<Local>) -> Option<DueStatus> {
+        let due = self.due?;
+        if self.completed {
+            return None;
+        }
+
+        if due < now {
+            Some(DueStatus::Overdue)
+        } else if due.date_naive() == now.date_naive() {
+            Some(DueStatus::DueToday)
+        } else {
+            Some(DueStatus::Upcoming)
+        }
+    }
+
+    /// Number of completed subtasks and the total number of subtasks.
+    pub fn subtask_progress(&self) -> (usize, usize) {
+        let done = self.subtasks.iter().filter(|subtask| subtask.completed).count();
+        (done, self.subtasks.len())
+    }
+}
+
+/// Splits `#tag` tokens out of `input`, returning the remaining title and the
+/// lowercased tags.
+pub fn parse_tags(input: &str) -> (String, BTreeSet<String>) {
+    let mut tags = BTreeSet::new();
+    let mut words = Vec::new();
+
+    for word in input.split_whitespace() {
+        match word.strip_prefix('#') {
+            Some(tag) if !tag.is_empty() && !tag.contains('#') => {
+                tags.insert(tag.to_lowercase());
+            }
+            _ => words.push(word),
+        }
+    }
+
+    (words.join(" "), tags)
+}
+
+/// The last minute of `date` in local time, used as the default due time.
+pub fn end_of_day(date: NaiveDate) -> Option<DateTime<Local>> {
+    date.and_hms_opt(23, 59, 0)?
+        .and_local_timezone(Local)
+        .earliest()
+}
+
+/// Reads a typed due date such as `2026-12-24`, `24.12.2026` or either
+/// followed by a time like `18:30`. Without a time it is due at the end of
+/// the day.
+pub fn parse_due(input: &str) -> Option<DateTime<Local>> {
+    let input = input.trim();
+    let (date, time) = match input.split_once(char::is_whitespace) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None => (input, None),
+    };
+    let date = ["%Y-%m-%d", "%d.%m.%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())?;
+    match time {
+        Some(time) => date
+            .and_time(NaiveTime::parse_from_str(time, "%H:%M").ok()?)
+            .and_local_timezone(Local)
+            .earliest(),
+        None => end_of_day(date),
+    }
+}
+
+/// Everything that is written to `todos.json`. Transient UI state lives in
+/// `TodoState` so the on-disk format does not change with the UI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TodoDocument {
+    /// Layout version, see `migrations`.
+    pub version: u32,
+    pub todos: HashMap<TodoId, Todo>,
+    #[serde(default = "default_lists")]
+    pub lists: BTreeMap<usize, TodoList>,
+    #[serde(default = "default_next_list_id")]
+    pub next_list_id: usize,
+    #[serde(default)]
+    pub settings: Settings,
+    /// Version the document was loaded from if it had to be migrated. Backends
+    /// storing todos individually still hold the old layout, so the next save
+    /// rewrites everything.
+    #[serde(skip)]
+    pub migrated_from: Option<u32>,
+}
+
+/// User preferences stored alongside the todos.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub sort_order: SortOrder,
+    /// How many rotating backups of `todos.json` to keep
+    #[serde(default = "default_backup_count")]
+    pub backup_count: usize,
+    /// Days a deleted todo stays in the trash before it is purged on load,
+    /// 0 keeps it until the trash is emptied
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+    /// Days after completion when a todo is archived on load, 0 disables it
+    #[serde(default)]
+    pub auto_archive_days: u32,
+}
+
+fn default_backup_count() -> usize {
+    5
+}
+
+fn default_trash_retention_days() -> u32 {
+    30
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            sort_order: SortOrder::default(),
+            backup_count: default_backup_count(),
+            trash_retention_days: default_trash_retention_days(),
+            auto_archive_days: 0,
+        }
+    }
+}
+
+impl Default for TodoDocument {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            todos: HashMap::new(),
+            lists: default_lists(),
+            next_list_id: default_next_list_id(),
+            settings: Settings::default(),
+            migrated_from: None,
+        }
+    }
+}
+
+/// The document being edited together with the session-only UI state.
+#[derive(Debug, Clone)]
 pub struct TodoState {
-    todos: HashMap<usize, Todo>,
-    next_id: usize,
+    document: TodoDocument,
     filter: Filter,
     search_query: String,
-    editing: Option<(usize, String)>,
+    editing: Option<(EditTarget, String)>,
+    selected_tags: BTreeSet<String>,
+    tag_match: TagMatch,
+    expanded: BTreeSet<TodoId>,
+    subtask_inputs: HashMap<TodoId, String>,
+    due_inputs: HashMap<TodoId, String>,
+    selected_list: ListSelection,
+    new_list_name: String,
+    changes: ChangeSet,
+    history: History,
+    /// The description being typed into and the document from before the
+    /// first keystroke, recorded as one undo step once typing ends
+    typing: Option<(TodoId, TodoDocument)>,
+    notice: Option<Notice>,
+    /// Archived todos, `None` until the archive view loads them
+    archive: Option<BTreeMap<TodoId, Todo>>,
+    archive_error: Option<TodoError>,
+    /// Todos whose history panel is open
+    activity_open: BTreeSet<TodoId>,
+    /// Todos changed both here and in the file since it was last read
+    conflicts: Vec<Conflict>,
+    /// Another window is editing the file, edits are ignored
+    read_only: bool,
+    /// The CSV import and export panel, `None` while it is closed
+    csv: Option<CsvPanel>,
+}
+
+/// What the text in the main input is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditTarget {
+    NewTodo,
+    /// Renaming an existing todo
+    Todo(TodoId),
+}
+
+/// How long the snackbar offering to undo a deletion stays visible.
+pub const NOTICE_DURATION: StdDuration = StdDuration::from_secs(5);
+
+/// A transient message shown at the bottom of the window with an undo button.
+#[derive(Debug, Clone)]
+pub struct Notice {
+    pub text: String,
+    pub shown_at: Instant,
+    /// Whether `Message::Undo` reverts what it reports
+    pub undoable: bool,
+}
+
+/// Which list the todo view is scoped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ListSelection {
+    /// Every list that is not archived
+    #[default]
+    All,
+    List(usize),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -27,69 +382,496 @@ pub enum Filter {
     All,
     Active,
     Completed,
+    /// Deleted todos, from every list
+    Trash,
+    /// Archived todos, from every list, loaded on demand
+    Archive,
+}
+
+/// How the selected tags in the sidebar are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TagMatch {
+    /// Todos carrying at least one selected tag
+    #[default]
+    Any,
+    /// Todos carrying every selected tag
+    All,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Newest,
+    DueDate,
+    Priority,
+    RecentlyUpdated,
+    RecentlyCompleted,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 5] = [
+        SortOrder::Newest,
+        SortOrder::DueDate,
+        SortOrder::Priority,
+        SortOrder::RecentlyUpdated,
+        SortOrder::RecentlyCompleted,
+    ];
+}
+
+impl std::fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SortOrder::Newest => "Newest first",
+            SortOrder::DueDate => "Due date",
+            SortOrder::Priority => "Priority",
+            SortOrder::RecentlyUpdated => "Recently updated",
+            SortOrder::RecentlyCompleted => "Recently completed",
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     AddTodo,
-    DeleteTodo(usize),
-    ToggleTodo(usize),
+    /// Moves a todo to the trash.
+    DeleteTodo(TodoId),
+    /// Takes a todo out of the trash, back into its list or the inbox if the
+    /// list is gone.
+    RestoreTodo(TodoId),
+    DeletePermanently(TodoId),
+    EmptyTrash,
+    TrashRetentionChanged(u32),
+    /// Moves the completed todos of the selected list into the archive.
+    ArchiveCompleted,
+    /// Takes a todo out of the archive, back into its list or the inbox.
+    UnarchiveTodo(TodoId),
+    AutoArchiveChanged(u32),
+    /// Completing a todo completes all of its subtasks, reopening it leaves
+    /// them untouched. Completing a recurring todo spawns its next instance.
+    ToggleTodo(TodoId),
     FilterChanged(Filter),
     SearchQueryChanged(String),
-    EditingTodo(usize, String),
+    EditingTodo(TodoId, String),
     InputChanged(String),
-    DescriptionChanged(usize, String),
+    DescriptionChanged(TodoId, String),
     FinishEditing,
     CancelEditing,
+    SetDueDate(TodoId, DateTime<Local>),
+    ShiftDueDate(TodoId, Duration),
+    ClearDueDate(TodoId),
+    DueInputChanged(TodoId, String),
+    /// Sets the due date typed into the todo's date field, see `parse_due`.
+    /// Input that does not parse is left in the field.
+    SubmitDueDate(TodoId),
+    PriorityChanged(TodoId, Priority),
+    RemoveTag(TodoId, String),
+    ToggleTagFilter(String),
+    TagMatchChanged(TagMatch),
+    ClearTagFilter,
+    ToggleExpanded(TodoId),
+    ToggleActivity(TodoId),
+    /// Copies the history of a todo to the clipboard as JSON, handled by the
+    /// application since it needs a command.
+    ExportActivity(TodoId),
+    SubtaskInputChanged(TodoId, String),
+    AddSubtask(TodoId),
+    /// The parent is completed once every subtask is done and reopened as
+    /// soon as one of them is unchecked again.
+    ToggleSubtask(TodoId, usize),
+    DeleteSubtask(TodoId, usize),
+    RecurrenceChanged(TodoId, Option<Recurrence>),
+    SelectList(ListSelection),
+    NewListNameChanged(String),
+    CreateList,
+    MoveTodo(TodoId, usize),
+    SetListArchived(usize, bool),
+    /// Removes a list and moves every todo in it to the trash. The inbox is kept.
+    DeleteList(usize),
+    SortChanged(SortOrder),
+    Undo,
+    Redo,
+    DismissNotice,
+    /// Periodic check whether the snackbar has expired
+    NoticeTick(Instant),
+    /// Settles a todo changed both here and in the file.
+    ResolveConflict(TodoId, Resolution),
+    ResolveAllConflicts(Resolution),
+    ToggleCsvPanel,
+    CsvPathChanged(String),
+    /// Writes todos to the file in the CSV panel, as todo.txt if it ends in
+    /// `.txt`. Handled by the application since it needs a command.
+    ExportCsv(ExportScope),
+    /// Reads the CSV or todo.txt file into the import wizard, handled by the
+    /// application.
+    ImportCsv,
+    ImportFieldChanged(usize, Field),
+    ImportDuplicatesChanged(DuplicateAction),
+    /// Adds the previewed rows to the document, creating missing lists.
+    ConfirmImport,
+    CancelImport,
+}
+
+impl Message {
+    /// The todo modified by a message, if any.
+    fn todo_id(&self) -> Option<TodoId> {
+        match self {
+            Message::DeleteTodo(id)
+            | Message::RestoreTodo(id)
+            | Message::DeletePermanently(id)
+            | Message::ToggleTodo(id)
+            | Message::DescriptionChanged(id, _)
+            | Message::SetDueDate(id, _)
+            | Message::ShiftDueDate(id, _)
+            | Message::ClearDueDate(id)
+            | Message::SubmitDueDate(id)
+            | Message::PriorityChanged(id, _)
+            | Message::RemoveTag(id, _)
+            | Message::AddSubtask(id)
+            | Message::ToggleSubtask(id, _)
+            | Message::DeleteSubtask(id, _)
+            | Message::RecurrenceChanged(id, _)
+            | Message::MoveTodo(id, _) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Whether a message changes the document beyond single todos.
+    fn changes_metadata(&self) -> bool {
+        matches!(
+            self,
+            Message::CreateList
+                | Message::SetListArchived(..)
+                | Message::DeleteList(_)
+                | Message::SortChanged(_)
+                | Message::TrashRetentionChanged(_)
+                | Message::AutoArchiveChanged(_)
+        )
+    }
+
+    /// Whether a message edits the document and can be undone. The sort
+    /// order is a preference and is left out.
+    fn is_undoable(&self) -> bool {
+        self.todo_id().is_some()
+            || matches!(
+                self,
+                Message::AddTodo
+                    | Message::FinishEditing
+                    | Message::CreateList
+                    | Message::SetListArchived(..)
+                    | Message::DeleteList(_)
+                    | Message::EmptyTrash
+                    | Message::ConfirmImport
+            )
+    }
+
+    /// Whether a message changes what is saved, or starts renaming a todo,
+    /// so it is ignored while the document is read-only.
+    fn edits_document(&self) -> bool {
+        self.is_undoable()
+            || self.changes_metadata()
+            || matches!(
+                self,
+                Message::EditingTodo(..)
+                    | Message::ArchiveCompleted
+                    | Message::UnarchiveTodo(_)
+                    | Message::Undo
+                    | Message::Redo
+                    | Message::ResolveConflict(..)
+                    | Message::ResolveAllConflicts(_)
+            )
+    }
 }
 
 impl Default for TodoState {
     fn default() -> Self {
+        Self::new(TodoDocument::default())
+    }
+}
+
+impl TodoState {
+    pub fn new(document: TodoDocument) -> Self {
         Self {
-            todos: HashMap::new(),
-            next_id: 1,
+            document,
             filter: Filter::All,
             search_query: String::new(),
             editing: None,
+            selected_tags: BTreeSet::new(),
+            tag_match: TagMatch::default(),
+            expanded: BTreeSet::new(),
+            subtask_inputs: HashMap::new(),
+            due_inputs: HashMap::new(),
+            selected_list: ListSelection::default(),
+            new_list_name: String::new(),
+            changes: ChangeSet::default(),
+            history: History::default(),
+            typing: None,
+            notice: None,
+            archive: None,
+            archive_error: None,
+            activity_open: BTreeSet::new(),
+            conflicts: Vec::new(),
+            read_only: false,
+            csv: None,
         }
     }
 }
 
+/// A parsed search box entry: free text plus qualifiers such as `updated:7d`
+/// that keep todos whose timestamp lies within the last hours (`h`), days
+/// (`d`) or weeks (`w`). `completed:` only keeps completed todos.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SearchQuery {
+    /// Lowercased words matched against title, description and tags
+    pub text: String,
+    pub created_within: Option<Duration>,
+    pub updated_within: Option<Duration>,
+    pub completed_within: Option<Duration>,
+}
+
+impl SearchQuery {
+    /// Splits qualifiers off the query. Words that only look like
+    /// qualifiers (`note:x`) stay part of the text.
+    pub fn parse(input: &str) -> Self {
+        let mut query = SearchQuery::default();
+        let mut words = Vec::new();
+        for word in input.split_whitespace() {
+            let qualifier = word.split_once(':').and_then(|(key, value)| {
+                let within = parse_age(value)?;
+                match key.to_lowercase().as_str() {
+                    "created" => query.created_within = Some(within),
+                    "updated" => query.updated_within = Some(within),
+                    "completed" => query.completed_within = Some(within),
+                    _ => return None,
+                }
+                Some(())
+            });
+            if qualifier.is_none() {
+                words.push(word.to_lowercase());
+            }
+        }
+        query.text = words.join(" ");
+        query
+    }
+
+    pub fn matches(&self, todo: &Todo, now: DateTime<Local>) -> bool {
+        let within = |time: DateTime<Local>, age: Option<Duration>| {
+            age.is_none_or(|age| now - time <= age)
+        };
+        let completed = match self.completed_within {
+            Some(age) => todo.completed && todo.completed_at.is_some_and(|time| now - time <= age),
+            None => true,
+        };
+
+        completed
+            && within(todo.created_at, self.created_within)
+            && within(todo.last_updated(), self.updated_within)
+            && (self.text.is_empty()
+                || todo.title.to_lowercase().contains(&self.text)
+                || todo.description.to_lowercase().contains(&self.text)
+                || todo.tags.iter().any(|tag| tag.contains(&self.text)))
+    }
+}
+
+/// Parses ages like `12h`, `7d` or `2w`.
+fn parse_age(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let amount = i64::from(value[..value.len() - unit.len_utf8()].parse::<u32>().ok()?);
+    match unit.to_ascii_lowercase() {
+        'h' => Some(Duration::hours(amount)),
+        'd' => Some(Duration::days(amount)),
+        'w' => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
+/// Earliest due date first, todos without a due date last (newest first).
+fn cmp_due(a: &Todo, b: &Todo) -> std::cmp::Ordering {
+    match (a.due, b.due) {
+        (Some(a_due), Some(b_due)) => a_due.cmp(&b_due),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => b.created_at.cmp(&a.created_at),
+    }
+}
+
 impl TodoState {
+    /// Applies a message, recording which parts of the document it changed.
     pub fn update(&mut self, message: Message) {
+        if self.read_only && message.edits_document() {
+            return;
+        }
+        if let Some(id) = message.todo_id() {
+            self.changes.todos.insert(id);
+        }
+        self.changes.metadata |= message.changes_metadata();
+
+        // Typing into a description is one undo step, so the document is
+        // copied once for it rather than on every keystroke
+        if let Message::DescriptionChanged(id, _) = message {
+            if self.typing.as_ref().is_none_or(|(typing, _)| *typing != id) {
+                self.finish_typing();
+                self.typing = Some((id, self.document.clone()));
+            }
+            self.apply(message);
+            return;
+        }
+        if !matches!(message, Message::NoticeTick(_)) {
+            self.finish_typing();
+        }
+
+        let before = message.is_undoable().then(|| self.document.clone());
+        // Imported todos keep the times they were exported with
+        let stamp_new = !matches!(message, Message::ConfirmImport);
+
+        self.apply(message);
+
+        if let Some(before) = before {
+            if before != self.document {
+                self.touch_changed(&before, stamp_new);
+                self.history.record(before);
+            }
+        }
+    }
+
+    /// Ends typing into a description, which happens on any other message
+    /// and when the changes are taken for saving after a pause.
+    fn finish_typing(&mut self) {
+        let Some((id, before)) = self.typing.take() else {
+            return;
+        };
+        if before.todos.get(&id) != self.document.todos.get(&id) {
+            self.touch_changed(&before, true);
+            self.history.record(before);
+        }
+    }
+
+    /// Whether the document changed since the last `take_changes`.
+    pub fn has_changes(&self) -> bool {
+        !self.changes.is_empty()
+    }
+
+    /// Hands out the changes recorded since the last call, for saving.
+    pub fn take_changes(&mut self) -> ChangeSet {
+        self.finish_typing();
+        std::mem::take(&mut self.changes)
+    }
+
+    fn apply(&mut self, message: Message) {
         match message {
             Message::AddTodo => {
-                if let Some((_, title)) = &self.editing {
-                    if !title.trim().is_empty() {
-                        let id = self.next_id;
-                        self.todos.insert(
+                if let Some((_, input)) = &self.editing {
+                    let (title, tags) = parse_tags(input);
+                    if !title.is_empty() {
+                        let id = TodoId::random();
+                        self.document.todos.insert(
                             id,
                             Todo {
                                 id,
-                                title: title.clone(),
+                                title,
                                 description: String::new(),
                                 completed: false,
                                 created_at: Local::now(),
+                                completed_at: None,
+                                updated_at: None,
+                                due: None,
+                                priority: Priority::None,
+                                tags,
+                                subtasks: Vec::new(),
+                                recurrence: None,
+                                list_id: match self.selected_list {
+                                    ListSelection::List(list_id) => list_id,
+                                    ListSelection::All => INBOX_LIST_ID,
+                                },
+                                series: None,
+                                deleted_at: None,
+                                activity: Vec::new(),
                             },
                         );
-                        self.next_id += 1;
-                        self.editing = Some((0, String::new()));
+                        self.editing = Some((EditTarget::NewTodo, String::new()));
                     }
                 } else {
-                    self.editing = Some((0, String::new()));
+                    self.editing = Some((EditTarget::NewTodo, String::new()));
                 }
             }
             Message::DeleteTodo(id) => {
-                self.todos.remove(&id);
-                if let Some((editing_id, _)) = self.editing {
+                let Some(todo) = self.document.todos.get_mut(&id) else {
+                    return;
+                };
+                if todo.deleted_at.is_none() {
+                    todo.deleted_at = Some(Local::now());
+                    let notice = format!("Moved \"{}\" to the trash", todo.title);
+                    self.show_notice(notice);
+                }
+                self.expanded.remove(&id);
+                self.subtask_inputs.remove(&id);
+                self.due_inputs.remove(&id);
+                if let Some((EditTarget::Todo(editing_id), _)) = self.editing {
                     if editing_id == id {
                         self.editing = None;
                     }
                 }
             }
+            Message::RestoreTodo(id) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    todo.deleted_at = None;
+                    if !self.document.lists.contains_key(&todo.list_id) {
+                        todo.list_id = INBOX_LIST_ID;
+                    }
+                }
+            }
+            Message::DeletePermanently(id) => {
+                self.document.todos.remove(&id);
+            }
+            Message::EmptyTrash => {
+                let changes = &mut self.changes;
+                self.document.todos.retain(|id, todo| {
+                    if todo.is_trashed() {
+                        changes.todos.insert(*id);
+                    }
+                    !todo.is_trashed()
+                });
+            }
+            Message::TrashRetentionChanged(days) => {
+                self.document.settings.trash_retention_days = days;
+            }
+            Message::ArchiveCompleted => {
+                let ids: Vec<TodoId> = self
+                    .document
+                    .todos
+                    .values()
+                    .filter(|todo| todo.completed && self.in_selected_list(todo))
+                    .map(|todo| todo.id)
+                    .collect();
+                self.archive_todos(&ids);
+                if !ids.is_empty() {
+                    // Archiving clears the history, so there is nothing to undo
+                    self.show_notice(format!("Archived {} completed todos", ids.len()));
+                    if let Some(notice) = &mut self.notice {
+                        notice.undoable = false;
+                    }
+                }
+            }
+            Message::UnarchiveTodo(id) => {
+                let Some(mut todo) = self.archive.as_mut().and_then(|archive| archive.remove(&id))
+                else {
+                    return;
+                };
+                if !self.document.lists.contains_key(&todo.list_id) {
+                    todo.list_id = INBOX_LIST_ID;
+                }
+                activity::record(&mut todo, Change::Unarchived, Local::now());
+                self.document.todos.insert(id, todo);
+                self.changes.unarchive(id);
+                // Undoing past this point would bring back stale copies
+                self.history.clear();
+            }
+            Message::AutoArchiveChanged(days) => {
+                self.document.settings.auto_archive_days = days;
+            }
             Message::ToggleTodo(id) => {
-                if let Some(todo) = self.todos.get_mut(&id) {
-                    todo.completed = !todo.completed;
+                if let Some(todo) = self.document.todos.get(&id) {
+                    let completed = !todo.completed;
+                    self.set_completed(id, completed);
                 }
             }
             Message::FilterChanged(filter) => {
@@ -99,7 +881,7 @@ impl TodoState {
                 self.search_query = query;
             }
             Message::EditingTodo(id, current_text) => {
-                self.editing = Some((id, current_text));
+                self.editing = Some((EditTarget::Todo(id), current_text));
             }
             Message::InputChanged(text) => {
                 if let Some((id, _)) = self.editing {
@@ -107,16 +889,18 @@ impl TodoState {
                 }
             }
             Message::DescriptionChanged(id, text) => {
-                if let Some(todo) = self.todos.get_mut(&id) {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
                     todo.description = text;
                 }
             }
             Message::FinishEditing => {
-                if let Some((id, title)) = &self.editing {
-                    let title = title.trim();
-                    if *id > 0 && !title.is_empty() {
-                        if let Some(todo) = self.todos.get_mut(id) {
-                            todo.title = title.to_string();
+                if let Some((target, input)) = &self.editing {
+                    let (title, tags) = parse_tags(input);
+                    if let (EditTarget::Todo(id), false) = (target, title.is_empty()) {
+                        if let Some(todo) = self.document.todos.get_mut(id) {
+                            todo.title = title;
+                            todo.tags.extend(tags);
+                            self.changes.todos.insert(*id);
                         }
                     }
                     self.editing = None;
@@ -125,9 +909,604 @@ impl TodoState {
             Message::CancelEditing => {
                 self.editing = None;
             }
+            Message::SetDueDate(id, due) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    todo.due = Some(due);
+                }
+            }
+            Message::ShiftDueDate(id, offset) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    todo.due = todo.due.map(|due| due + offset);
+                }
+            }
+            Message::ClearDueDate(id) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    todo.due = None;
+                }
+            }
+            Message::DueInputChanged(id, text) => {
+                self.due_inputs.insert(id, text);
+            }
+            Message::SubmitDueDate(id) => {
+                let due = self.due_inputs.get(&id).and_then(|input| parse_due(input));
+                if let (Some(todo), Some(due)) = (self.document.todos.get_mut(&id), due) {
+                    todo.due = Some(due);
+                    self.due_inputs.remove(&id);
+                }
+            }
+            Message::PriorityChanged(id, priority) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    todo.priority = priority;
+                }
+            }
+            Message::RemoveTag(id, tag) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    todo.tags.remove(&tag);
+                }
+            }
+            Message::ToggleTagFilter(tag) => {
+                if !self.selected_tags.remove(&tag) {
+                    self.selected_tags.insert(tag);
+                }
+            }
+            Message::TagMatchChanged(tag_match) => {
+                self.tag_match = tag_match;
+            }
+            Message::ClearTagFilter => {
+                self.selected_tags.clear();
+            }
+            Message::ToggleExpanded(id) => {
+                if !self.expanded.remove(&id) {
+                    self.expanded.insert(id);
+                }
+            }
+            Message::ToggleActivity(id) => {
+                if !self.activity_open.remove(&id) {
+                    self.activity_open.insert(id);
+                }
+            }
+            Message::ExportActivity(_) => {}
+            Message::SubtaskInputChanged(id, text) => {
+                self.subtask_inputs.insert(id, text);
+            }
+            Message::AddSubtask(id) => {
+                let title = self.subtask_inputs.remove(&id).unwrap_or_default();
+                let title = title.trim();
+                if let (Some(todo), false) = (self.document.todos.get_mut(&id), title.is_empty()) {
+                    let subtask_id = todo.subtasks.iter().map(|subtask| subtask.id).max().unwrap_or(0);
+                    todo.subtasks.push(Subtask {
+                        id: subtask_id + 1,
+                        title: title.to_string(),
+                        completed: false,
+                    });
+                    // A new open item means the parent is no longer done
+                    if todo.completed {
+                        self.set_completed(id, false);
+                    }
+                }
+            }
+            Message::ToggleSubtask(id, subtask_id) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    let subtask = todo.subtasks.iter_mut().find(|subtask| subtask.id == subtask_id);
+                    if let Some(subtask) = subtask {
+                        subtask.completed = !subtask.completed;
+                    }
+                    let (done, total) = todo.subtask_progress();
+                    let completed = total > 0 && done == total;
+                    if completed != todo.completed {
+                        self.set_completed(id, completed);
+                    }
+                }
+            }
+            Message::DeleteSubtask(id, subtask_id) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    todo.subtasks.retain(|subtask| subtask.id != subtask_id);
+                }
+            }
+            Message::RecurrenceChanged(id, recurrence) => {
+                if let Some(todo) = self.document.todos.get_mut(&id) {
+                    if recurrence.is_some() && todo.due.is_none() {
+                        todo.due = end_of_day(Local::now().date_naive());
+                    }
+                    todo.recurrence = recurrence;
+                }
+            }
+            Message::SelectList(selection) => {
+                self.selected_list = selection;
+                self.selected_tags.clear();
+            }
+            Message::NewListNameChanged(name) => {
+                self.new_list_name = name;
+            }
+            Message::CreateList => {
+                let name = self.new_list_name.trim();
+                if !name.is_empty() {
+                    let id = self.document.next_list_id;
+                    self.document.lists.insert(
+                        id,
+                        TodoList {
+                            id,
+                            name: name.to_string(),
+                            archived: false,
+                        },
+                    );
+                    self.document.next_list_id += 1;
+                    self.new_list_name.clear();
+                    self.selected_list = ListSelection::List(id);
+                }
+            }
+            Message::MoveTodo(id, list_id) => {
+                if self.document.lists.contains_key(&list_id) {
+                    if let Some(todo) = self.document.todos.get_mut(&id) {
+                        todo.list_id = list_id;
+                    }
+                }
+            }
+            Message::SetListArchived(list_id, archived) => {
+                if list_id != INBOX_LIST_ID {
+                    if let Some(list) = self.document.lists.get_mut(&list_id) {
+                        list.archived = archived;
+                    }
+                    if archived && self.selected_list == ListSelection::List(list_id) {
+                        self.selected_list = ListSelection::All;
+                    }
+                }
+            }
+            Message::DeleteList(list_id) => {
+                if list_id == INBOX_LIST_ID {
+                    return;
+                }
+                if let Some(list) = self.document.lists.remove(&list_id) {
+                    self.show_notice(format!("Deleted list \"{}\"", list.name));
+                    let now = Local::now();
+                    for todo in self.document.todos.values_mut() {
+                        if todo.list_id == list_id && !todo.is_trashed() {
+                            todo.deleted_at = Some(now);
+                            self.changes.todos.insert(todo.id);
+                        }
+                    }
+                    if self.selected_list == ListSelection::List(list_id) {
+                        self.selected_list = ListSelection::All;
+                    }
+                }
+            }
+            Message::SortChanged(sort_order) => {
+                self.document.settings.sort_order = sort_order;
+            }
+            Message::Undo => {
+                if let Some(document) = self.history.undo(self.document.clone()) {
+                    self.restore(document);
+                }
+            }
+            Message::Redo => {
+                if let Some(document) = self.history.redo(self.document.clone()) {
+                    self.restore(document);
+                }
+            }
+            Message::DismissNotice => {
+                self.notice = None;
+            }
+            Message::NoticeTick(now) => {
+                if let Some(notice) = &self.notice {
+                    if now.duration_since(notice.shown_at) >= NOTICE_DURATION {
+                        self.notice = None;
+                    }
+                }
+            }
+            Message::ResolveConflict(id, resolution) => {
+                if let Some(index) = self.conflicts.iter().position(|conflict| conflict.id == id) {
+                    let conflict = self.conflicts.remove(index);
+                    self.resolve(conflict, resolution);
+                }
+            }
+            Message::ResolveAllConflicts(resolution) => {
+                for conflict in std::mem::take(&mut self.conflicts) {
+                    self.resolve(conflict, resolution);
+                }
+            }
+            Message::ToggleCsvPanel => {
+                self.csv = match self.csv {
+                    Some(_) => None,
+                    None => Some(CsvPanel::default()),
+                };
+            }
+            Message::CsvPathChanged(path) => {
+                if let Some(panel) = &mut self.csv {
+                    panel.path = path;
+                }
+            }
+            Message::ExportCsv(_) | Message::ImportCsv => {}
+            Message::ImportFieldChanged(index, field) => {
+                if let Some(wizard) = self.csv.as_mut().and_then(|panel| panel.import.as_mut()) {
+                    wizard.set_field(index, field);
+                }
+            }
+            Message::ImportDuplicatesChanged(action) => {
+                if let Some(wizard) = self.csv.as_mut().and_then(|panel| panel.import.as_mut()) {
+                    wizard.duplicates = action;
+                }
+            }
+            Message::ConfirmImport => {
+                if let Some(wizard) = self.csv.as_mut().and_then(|panel| panel.import.take()) {
+                    self.import(&wizard);
+                }
+            }
+            Message::CancelImport => {
+                if let Some(panel) = &mut self.csv {
+                    panel.import = None;
+                }
+            }
+        }
+    }
+
+    /// The merged document already holds the local version, so only taking
+    /// theirs changes it.
+    fn resolve(&mut self, conflict: Conflict, resolution: Resolution) {
+        if resolution == Resolution::KeepMine {
+            return;
+        }
+        let id = conflict.id;
+        match conflict.theirs {
+            Some(todo) => {
+                self.document.todos.insert(id, todo);
+                // The local side may have archived it meanwhile
+                self.changes.unarchive(id);
+                if let Some(archive) = &mut self.archive {
+                    archive.remove(&id);
+                }
+            }
+            None => {
+                self.document.todos.remove(&id);
+                self.changes.todos.insert(id);
+                if matches!(self.editing, Some((EditTarget::Todo(editing), _)) if editing == id) {
+                    self.editing = None;
+                }
+            }
+        }
+    }
+
+    /// Merges the document as changed outside the app into the one being
+    /// edited, both having started from `base`. `pending` holds changes not
+    /// saved yet, which are recomputed against `theirs`.
+    ///
+    /// Undo snapshots predate the external changes and are dropped, so
+    /// undoing cannot silently revert them.
+    pub fn merge_external(&mut self, base: &TodoDocument, theirs: &TodoDocument, mut pending: ChangeSet) {
+        pending.merge(self.take_changes());
+        let merge = merge::three_way(base, &self.document, theirs);
+
+        let mut changes = merge::changes(theirs, &merge.document);
+        for todo in pending.archived {
+            changes.archive(todo);
+        }
+        for id in pending.unarchived {
+            changes.unarchive(id);
+        }
+        self.changes = changes;
+        self.replace_document(merge.document);
+        self.history.clear();
+        self.notice = None;
+        self.conflicts = merge.conflicts;
+    }
+
+    /// Adds the rows of the import wizard to the document. Duplicates are
+    /// skipped, updated in place or added as new todos with a fresh id.
+    fn import(&mut self, wizard: &ImportWizard) {
+        let fields = wizard.mapped_fields();
+        let (mut added, mut updated, mut skipped) = (0, 0, 0);
+        for row in wizard.preview(&self.document, Local::now()) {
+            let Ok(mut todo) = row.todo else {
+                skipped += 1;
+                continue;
+            };
+            todo.list_id = match (&row.list, self.selected_list) {
+                (Some(name), _) => self.list_named(name),
+                (None, ListSelection::List(list_id)) => list_id,
+                (None, ListSelection::All) => INBOX_LIST_ID,
+            };
+            match (row.duplicate, wizard.duplicates) {
+                (Some(_), DuplicateAction::Skip) => skipped += 1,
+                (Some(id), DuplicateAction::Update) => {
+                    if let Some(existing) = self.document.todos.get_mut(&id) {
+                        csv_io::update_fields(existing, &todo, &fields);
+                        self.changes.todos.insert(id);
+                        updated += 1;
+                    }
+                }
+                _ => {
+                    // Also covers ids of trashed todos, which are not duplicates
+                    if self.document.todos.contains_key(&todo.id) {
+                        todo.id = TodoId::random();
+                    }
+                    self.document.todos.insert(todo.id, todo);
+                    added += 1;
+                }
+            }
+        }
+        self.show_notice(format!(
+            "Imported {} todos, updated {}, skipped {}",
+            added, updated, skipped
+        ));
+    }
+
+    /// The id of the list called `name`, ignoring case, created if missing.
+    fn list_named(&mut self, name: &str) -> usize {
+        let existing = self
+            .document
+            .lists
+            .values()
+            .find(|list| list.name.to_lowercase() == name.to_lowercase());
+        if let Some(list) = existing {
+            return list.id;
+        }
+        let id = self.document.next_list_id;
+        self.document.lists.insert(
+            id,
+            TodoList {
+                id,
+                name: name.to_string(),
+                archived: false,
+            },
+        );
+        self.document.next_list_id += 1;
+        self.changes.metadata = true;
+        id
+    }
+
+    pub fn csv_panel(&self) -> Option<&CsvPanel> {
+        self.csv.as_ref()
+    }
+
+    /// The todos of `scope` as CSV, and how many there are.
+    pub fn export_csv(&self, scope: ExportScope) -> Result<(String, usize)> {
+        let todos = self.exported_todos(scope);
+        csv_io::export(&todos, &self.document.lists).map(|data| (data, todos.len()))
+    }
+
+    /// The todos of `scope` as todo.txt lines, and how many there are.
+    pub fn export_todo_txt(&self, scope: ExportScope) -> (String, usize) {
+        let todos = self.exported_todos(scope);
+        (todotxt::write_todos(&todos, &self.document.lists), todos.len())
+    }
+
+    fn exported_todos(&self, scope: ExportScope) -> Vec<&Todo> {
+        match scope {
+            ExportScope::All => {
+                let mut todos: Vec<&Todo> = self
+                    .document
+                    .todos
+                    .values()
+                    .filter(|todo| !todo.is_trashed())
+                    .collect();
+                todos.sort_by_key(|todo| todo.created_at);
+                todos
+            }
+            ExportScope::Filtered => self.filtered_todos(),
+        }
+    }
+
+    /// Shows how the export went in the CSV panel.
+    pub fn csv_exported(&mut self, result: std::result::Result<usize, TodoError>) {
+        if let Some(panel) = &mut self.csv {
+            panel.status = Some(match result {
+                Ok(count) => format!("Exported {} todos to {}", count, panel.path.trim()),
+                Err(error) => error.to_string(),
+            });
+        }
+    }
+
+    /// Opens the import wizard on a CSV file that was read.
+    pub fn start_import(&mut self, table: std::result::Result<CsvTable, TodoError>) {
+        if let Some(panel) = &mut self.csv {
+            match table {
+                Ok(table) => {
+                    panel.import = Some(ImportWizard::new(table));
+                    panel.status = None;
+                }
+                Err(error) => panel.status = Some(error.to_string()),
+            }
+        }
+    }
+
+    /// How the rows of the import wizard would be imported right now.
+    pub fn import_preview(&self) -> Vec<ImportRow> {
+        self.csv
+            .as_ref()
+            .and_then(|panel| panel.import.as_ref())
+            .map(|wizard| wizard.preview(&self.document, Local::now()))
+            .unwrap_or_default()
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+        if read_only {
+            self.editing = None;
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Moves todos out of the document into the archive. Undo snapshots
+    /// still holding them are dropped so they cannot come back twice.
+    fn archive_todos(&mut self, ids: &[TodoId]) {
+        for id in ids {
+            let Some(mut todo) = self.document.todos.remove(id) else {
+                continue;
+            };
+            activity::record(&mut todo, Change::Archived, Local::now());
+            self.expanded.remove(id);
+            self.subtask_inputs.remove(id);
+            self.due_inputs.remove(id);
+            if let Some(archive) = &mut self.archive {
+                archive.insert(*id, todo.clone());
+            }
+            self.changes.archive(todo);
+        }
+        if !ids.is_empty() {
+            self.history.clear();
+        }
+    }
+
+    /// Archives todos completed longer ago than the auto-archive setting.
+    /// Todos completed before completion times were recorded count from
+    /// their creation.
+    pub fn auto_archive(&mut self, now: DateTime<Local>) {
+        let days = self.document.settings.auto_archive_days;
+        if days == 0 {
+            return;
+        }
+        let cutoff = now - Duration::days(i64::from(days));
+        let ids: Vec<TodoId> = self
+            .document
+            .todos
+            .values()
+            .filter(|todo| {
+                todo.completed
+                    && !todo.is_trashed()
+                    && todo.completed_at.unwrap_or(todo.created_at) <= cutoff
+            })
+            .map(|todo| todo.id)
+            .collect();
+        self.archive_todos(&ids);
+    }
+
+    /// Hands the loaded archive to the archive view.
+    pub fn set_archive(&mut self, archive: std::result::Result<Vec<Todo>, TodoError>) {
+        match archive {
+            Ok(todos) => {
+                // Restored todos still in the archive file after an
+                // interrupted save are live again
+                let todos = todos
+                    .into_iter()
+                    .filter(|todo| !self.document.todos.contains_key(&todo.id))
+                    .map(|todo| (todo.id, todo));
+                self.archive = Some(todos.collect());
+                self.archive_error = None;
+            }
+            Err(error) => self.archive_error = Some(error),
+        }
+    }
+
+    pub fn archive_loaded(&self) -> bool {
+        self.archive.is_some()
+    }
+
+    pub fn archive_error(&self) -> Option<&TodoError> {
+        self.archive_error.as_ref()
+    }
+
+    pub fn auto_archive_days(&self) -> u32 {
+        self.document.settings.auto_archive_days
+    }
+
+    /// Whether the selected list has completed todos to archive.
+    pub fn has_completed(&self) -> bool {
+        self.document
+            .todos
+            .values()
+            .any(|todo| todo.completed && self.in_selected_list(todo))
+    }
+
+    /// Stamps `updated_at` on every todo that differs from `before` and logs
+    /// what changed in its history. Todos missing from `before` are only
+    /// stamped if `stamp_new` is set.
+    fn touch_changed(&mut self, before: &TodoDocument, stamp_new: bool) {
+        let now = Local::now();
+        let lists = &self.document.lists;
+        for (id, todo) in &mut self.document.todos {
+            let old = before.todos.get(id);
+            if old == Some(todo) {
+                continue;
+            }
+            // Todos created by the message, including spawned recurring instances
+            if old.is_none() {
+                self.changes.todos.insert(*id);
+                if !stamp_new {
+                    continue;
+                }
+            }
+            for change in activity::diff(old, todo, lists) {
+                activity::record(todo, change, now);
+            }
+            todo.updated_at = Some(now);
         }
     }
 
+    fn show_notice(&mut self, text: String) {
+        self.notice = Some(Notice {
+            text,
+            shown_at: Instant::now(),
+            undoable: true,
+        });
+    }
+
+    /// Switches to a snapshot from the undo history. Which todos differ is
+    /// not tracked, so the whole document is saved again.
+    fn restore(&mut self, document: TodoDocument) {
+        self.replace_document(document);
+        self.changes.full = true;
+        self.notice = None;
+    }
+
+    /// Marks a todo as completed or open. Completing checks off every subtask
+    /// and, for recurring todos, leaves the completed instance in place as
+    /// history while spawning the next instance with a shifted due date.
+    fn set_completed(&mut self, id: TodoId, completed: bool) {
+        let Some(todo) = self.document.todos.get_mut(&id) else {
+            return;
+        };
+
+        todo.completed = completed;
+        if !completed {
+            todo.completed_at = None;
+            return;
+        }
+        todo.completed_at = Some(Local::now());
+        for subtask in &mut todo.subtasks {
+            subtask.completed = true;
+        }
+
+        let Some(recurrence) = todo.recurrence.take() else {
+            return;
+        };
+        let now = Local::now();
+        let next_id = TodoId::random();
+        let next = Todo {
+            id: next_id,
+            title: todo.title.clone(),
+            description: todo.description.clone(),
+            completed: false,
+            created_at: now,
+            completed_at: None,
+            updated_at: None,
+            due: Some(recurrence.next_due(todo.due.unwrap_or(now), now)),
+            priority: todo.priority,
+            tags: todo.tags.clone(),
+            subtasks: todo
+                .subtasks
+                .iter()
+                .map(|subtask| Subtask {
+                    completed: false,
+                    ..subtask.clone()
+                })
+                .collect(),
+            recurrence: Some(recurrence),
+            list_id: todo.list_id,
+            series: Some(todo.series.unwrap_or(todo.id)),
+            deleted_at: None,
+            activity: Vec::new(),
+        };
+        todo.series = next.series;
+
+        self.document.todos.insert(next_id, next);
+    }
+
     pub fn search_query(&self) -> &String {
         &self.search_query
     }
@@ -136,62 +1515,272 @@ impl TodoState {
         &self.filter
     }
 
+    pub fn sort_order(&self) -> SortOrder {
+        self.document.settings.sort_order
+    }
+
+    pub fn selected_tags(&self) -> &BTreeSet<String> {
+        &self.selected_tags
+    }
+
+    pub fn tag_match(&self) -> TagMatch {
+        self.tag_match
+    }
+
+    pub fn is_expanded(&self, id: TodoId) -> bool {
+        self.expanded.contains(&id)
+    }
+
+    pub fn is_activity_open(&self, id: TodoId) -> bool {
+        self.activity_open.contains(&id)
+    }
+
+    /// The history of a todo as pretty-printed JSON, for exporting.
+    pub fn activity_json(&self, id: TodoId) -> Option<String> {
+        let todo = self.document.todos.get(&id)?;
+        serde_json::to_string_pretty(&serde_json::json!({
+            "id": todo.id,
+            "title": todo.title,
+            "activity": todo.activity,
+        }))
+        .ok()
+    }
+
+    pub fn subtask_input(&self, id: TodoId) -> &str {
+        self.subtask_inputs.get(&id).map_or("", String::as_str)
+    }
+
+    pub fn due_input(&self, id: TodoId) -> &str {
+        self.due_inputs.get(&id).map_or("", String::as_str)
+    }
+
+    pub fn lists(&self) -> impl Iterator<Item = &TodoList> {
+        self.document.lists.values()
+    }
+
+    pub fn selected_list(&self) -> ListSelection {
+        self.selected_list
+    }
+
+    pub fn new_list_name(&self) -> &str {
+        &self.new_list_name
+    }
+
+    /// Number of open todos and the total number of todos in a list.
+    pub fn list_counts(&self, list_id: usize) -> (usize, usize) {
+        let todos = self
+            .document
+            .todos
+            .values()
+            .filter(|todo| todo.list_id == list_id && !todo.is_trashed());
+        todos.fold((0, 0), |(open, total), todo| {
+            (open + usize::from(!todo.completed), total + 1)
+        })
+    }
+
+    pub fn trash_count(&self) -> usize {
+        self.document.todos.values().filter(|todo| todo.is_trashed()).count()
+    }
+
+    pub fn trash_retention_days(&self) -> u32 {
+        self.document.settings.trash_retention_days
+    }
+
+    /// Removes todos that have been in the trash longer than the retention
+    /// period, recording them as changed so the removal is saved.
+    pub fn purge_trash(&mut self, now: DateTime<Local>) {
+        let days = self.document.settings.trash_retention_days;
+        if days == 0 {
+            return;
+        }
+        let cutoff = now - Duration::days(i64::from(days));
+        let changes = &mut self.changes;
+        self.document.todos.retain(|id, todo| match todo.deleted_at {
+            Some(deleted_at) if deleted_at <= cutoff => {
+                changes.todos.insert(*id);
+                false
+            }
+            _ => true,
+        });
+    }
+
+    /// Whether a live todo belongs to the currently selected list.
+    fn in_selected_list(&self, todo: &Todo) -> bool {
+        if todo.is_trashed() {
+            return false;
+        }
+        match self.selected_list {
+            ListSelection::List(list_id) => todo.list_id == list_id,
+            ListSelection::All => self
+                .document
+                .lists
+                .get(&todo.list_id)
+                .is_some_and(|list| !list.archived),
+        }
+    }
+
+    /// Every tag in use in the selected list together with the number of todos
+    /// carrying it.
+    pub fn all_tags(&self) -> BTreeMap<&str, usize> {
+        let mut tags = BTreeMap::new();
+        let todos = self.document.todos.values().filter(|todo| self.in_selected_list(todo));
+        for tag in todos.flat_map(|todo| &todo.tags) {
+            *tags.entry(tag.as_str()).or_insert(0) += 1;
+        }
+        tags
+    }
+
     pub fn filtered_todos(&self) -> Vec<&Todo> {
-        let mut todos: Vec<&Todo> = self.todos.values().collect();
+        match self.filter {
+            Filter::Trash => return self.trashed_todos(),
+            Filter::Archive => return self.archived_todos(),
+            _ => {}
+        }
+
+        let mut todos: Vec<&Todo> = self
+            .document
+            .todos
+            .values()
+            .filter(|todo| self.in_selected_list(todo))
+            .collect();
         
         // Apply filter
         todos.retain(|todo| match self.filter {
             Filter::All => true,
             Filter::Active => !todo.completed,
             Filter::Completed => todo.completed,
+            Filter::Trash | Filter::Archive => false,
         });
         
+        // Apply tag selection from the sidebar
+        if !self.selected_tags.is_empty() {
+            todos.retain(|todo| match self.tag_match {
+                TagMatch::Any => self.selected_tags.iter().any(|tag| todo.tags.contains(tag)),
+                TagMatch::All => self.selected_tags.iter().all(|tag| todo.tags.contains(tag)),
+            });
+        }
+
         // Apply search if there's a query
         if !self.search_query.is_empty() {
-            let query = self.search_query.to_lowercase();
-            todos.retain(|todo| {
-                todo.title.to_lowercase().contains(&query)
-                    || todo.description.to_lowercase().contains(&query)
-            });
+            let query = SearchQuery::parse(&self.search_query);
+            let now = Local::now();
+            todos.retain(|todo| query.matches(todo, now));
         }
         
-        // Sort by creation date (newest first)
-        todos.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        match self.document.settings.sort_order {
+            // Sort by creation date (newest first)
+            SortOrder::Newest => todos.sort_by_key(|todo| std::cmp::Reverse(todo.created_at)),
+            SortOrder::DueDate => todos.sort_by(|a, b| cmp_due(a, b)),
+            // Highest priority first, ties broken by due date
+            SortOrder::Priority => {
+                todos.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| cmp_due(a, b)))
+            }
+            SortOrder::RecentlyUpdated => {
+                todos.sort_by_key(|todo| std::cmp::Reverse(todo.last_updated()))
+            }
+            // Open todos have no completion time and come last
+            SortOrder::RecentlyCompleted => {
+                todos.sort_by_key(|todo| std::cmp::Reverse((todo.completed_at, todo.created_at)))
+            }
+        }
         
         todos
     }
 
-    pub fn editing(&self) -> Option<(usize, &String)> {
+    /// Deleted todos matching the search, most recently deleted first.
+    fn trashed_todos(&self) -> Vec<&Todo> {
+        let query = SearchQuery::parse(&self.search_query);
+        let now = Local::now();
+        let mut todos: Vec<&Todo> = self
+            .document
+            .todos
+            .values()
+            .filter(|todo| todo.is_trashed() && query.matches(todo, now))
+            .collect();
+        todos.sort_by_key(|todo| std::cmp::Reverse(todo.deleted_at));
+        todos
+    }
+
+    /// Archived todos matching the search, most recently completed first.
+    fn archived_todos(&self) -> Vec<&Todo> {
+        let Some(archive) = &self.archive else {
+            return Vec::new();
+        };
+        let query = SearchQuery::parse(&self.search_query);
+        let now = Local::now();
+        let mut todos: Vec<&Todo> = archive
+            .values()
+            .filter(|todo| query.matches(todo, now))
+            .collect();
+        todos.sort_by_key(|todo| std::cmp::Reverse(todo.completed_at.unwrap_or(todo.created_at)));
+        todos
+    }
+
+    pub fn editing(&self) -> Option<(EditTarget, &String)> {
         self.editing.as_ref().map(|(id, text)| (*id, text))
     }
 
-    fn data_path() -> PathBuf {
-        let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
-        path.push("todo_gui");
-        fs::create_dir_all(&path).ok();
-        path.push("todos.json");
-        path
+    pub fn notice(&self) -> Option<&Notice> {
+        self.notice.as_ref()
     }
 
-    pub async fn save(&self) -> Result<(), String> {
-        let json = serde_json::to_string_pretty(self)
-            .map_err(|e| format!("Failed to serialize todos: {}", e))?;
-        
-        fs::write(Self::data_path(), json)
-            .map_err(|e| format!("Failed to save todos: {}", e))
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo() || self.typing.is_some()
     }
 
-    pub async fn load() -> Result<Self, String> {
-        let path = Self::data_path();
-        
-        if !path.exists() {
-            return Ok(Self::default());
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    pub fn document(&self) -> &TodoDocument {
+        &self.document
+    }
+
+    /// Replaces the document, keeping the UI state that still applies to it.
+    pub fn set_document(&mut self, document: TodoDocument) {
+        self.changes = match document.migrated_from {
+            Some(_) => ChangeSet::full(),
+            None => ChangeSet::default(),
+        };
+        self.replace_document(document);
+        self.history.clear();
+        self.notice = None;
+        self.conflicts.clear();
+    }
+
+    fn replace_document(&mut self, document: TodoDocument) {
+        self.document = document;
+        self.typing = None;
+        if let ListSelection::List(list_id) = self.selected_list {
+            if !self.document.lists.contains_key(&list_id) {
+                self.selected_list = ListSelection::All;
+            }
+        }
+        if let Some((EditTarget::Todo(id), _)) = self.editing {
+            if !self.document.todos.contains_key(&id) {
+                self.editing = None;
+            }
         }
-        
-        let data = fs::read_to_string(path)
-            .map_err(|e| format!("Failed to read todo file: {}", e))?;
-        
-        serde_json::from_str(&data)
-            .map_err(|e| format!("Failed to parse todos: {}", e))
     }
-} 
\ No newline at end of file
+}
+
+impl TodoDocument {
+    /// Parses a saved file of any known version, migrating it to the current layout.
+    pub fn from_json(data: &str) -> Result<Self> {
+        let value = serde_json::from_str(data)
+            .map_err(|e| TodoError::ParseError(e.to_string()))?;
+
+        Self::from_value(value)
+    }
+
+    /// Migrates and deserializes an already parsed document.
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let version = migrations::detect_version(&value)?;
+        let value = migrations::migrate(value)?;
+
+        let mut document: Self = serde_json::from_value(value)
+            .map_err(|e| TodoError::ParseError(e.to_string()))?;
+        document.migrated_from = (version < CURRENT_VERSION).then_some(version);
+        Ok(document)
+    }
+}
@@ -0,0 +1,326 @@
+use chrono::{DateTime, Local, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use crate::todo::{end_of_day, Priority, Todo, TodoDocument, TodoId, TodoList, INBOX_LIST_ID};
+
+/// Date format of todo.txt, also used in `due:`.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Whether `path` is read and written as todo.txt rather than CSV.
+pub fn is_todo_txt(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("txt"))
+}
+
+/// One line of a todo.txt file, before it is matched to a `Todo`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Task {
+    pub completed: bool,
+    pub completion_date: Option<NaiveDate>,
+    pub creation_date: Option<NaiveDate>,
+    /// `(A)`, or `pri:A` on completed tasks
+    pub priority: Option<char>,
+    /// The words that are not projects, contexts or known keys, in order.
+    /// Unknown `key:value` pairs stay part of it, as do words escaped with
+    /// a leading `\`, which is dropped.
+    pub title: String,
+    /// The first `+project`, further ones stay in the title
+    pub project: Option<String>,
+    /// `@context`s, lowercased
+    pub contexts: Vec<String>,
+    pub due: Option<NaiveDate>,
+    pub id: Option<TodoId>,
+}
+
+impl Task {
+    /// Splits a line into its parts. Parts that fail to parse, such as a
+    /// `due:` without a valid date, are kept as words of the title.
+    pub fn parse(line: &str) -> Self {
+        let mut task = Task::default();
+        let mut words = line.split_whitespace().peekable();
+        let date = |word: Option<&&str>| {
+            word.and_then(|word| NaiveDate::parse_from_str(word, DATE_FORMAT).ok())
+        };
+
+        if words.peek() == Some(&"x") {
+            words.next();
+            task.completed = true;
+            task.completion_date = date(words.peek());
+            if task.completion_date.is_some() {
+                words.next();
+            }
+        } else if let Some(priority) = words.peek().and_then(|word| parse_priority_marker(word)) {
+            words.next();
+            task.priority = Some(priority);
+        }
+        task.creation_date = date(words.peek());
+        if task.creation_date.is_some() {
+            words.next();
+        }
+        // A single date after `x` is the completion date
+        if task.completed && task.creation_date.is_some() && task.completion_date.is_none() {
+            task.completion_date = task.creation_date.take();
+        }
+
+        let mut title = Vec::new();
+        for word in words {
+            if let Some(escaped) = word.strip_prefix('\\').filter(|escaped| needs_escape(escaped)) {
+                title.push(escaped);
+                continue;
+            }
+            if let Some(project) = word.strip_prefix('+').filter(|project| !project.is_empty()) {
+                if task.project.is_none() {
+                    task.project = Some(project.to_string());
+                    continue;
+                }
+            } else if let Some(context) = word.strip_prefix('@').filter(|context| !context.is_empty()) {
+                task.contexts.push(context.to_lowercase());
+                continue;
+            } else if let Some((key, value)) = word.split_once(':') {
+                let known = match key {
+                    "due" => NaiveDate::parse_from_str(value, DATE_FORMAT)
+                        .ok()
+                        .map(|due| task.due = Some(due)),
+                    "id" => value.parse().ok().map(|id| task.id = Some(id)),
+                    "pri" => parse_priority_letter(value).map(|priority| task.priority = Some(priority)),
+                    _ => None,
+                };
+                if known.is_some() {
+                    continue;
+                }
+            }
+            title.push(word);
+        }
+        task.title = title.join(" ");
+        task
+    }
+}
+
+/// Whether a word of a title would be read back as something else: a
+/// project, a context, a known `key:value` or an escaped word.
+fn needs_escape(word: &str) -> bool {
+    if let Some(escaped) = word.strip_prefix('\\') {
+        return needs_escape(escaped);
+    }
+    if word.len() > 1 && word.starts_with(['+', '@']) {
+        return true;
+    }
+    match word.split_once(':') {
+        Some(("due", value)) => NaiveDate::parse_from_str(value, DATE_FORMAT).is_ok(),
+        Some(("id", value)) => value.parse::<TodoId>().is_ok(),
+        Some(("pri", value)) => parse_priority_letter(value).is_some(),
+        _ => false,
+    }
+}
+
+/// The title with every word that would be read back as something else
+/// escaped with `\`.
+fn escape_title(title: &str) -> String {
+    title
+        .split_whitespace()
+        .map(|word| if needs_escape(word) { format!("\\{}", word) } else { word.to_string() })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `(A)` to `(Z)`.
+fn parse_priority_marker(word: &str) -> Option<char> {
+    parse_priority_letter(word.strip_prefix('(')?.strip_suffix(')')?)
+}
+
+fn parse_priority_letter(value: &str) -> Option<char> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) if letter.is_ascii_uppercase() => Some(letter),
+        _ => None,
+    }
+}
+
+/// `(A)` is the most urgent. Letters past `(D)` are low as well.
+fn priority_from_letter(letter: Option<char>) -> Priority {
+    match letter {
+        None => Priority::None,
+        Some('A') => Priority::Urgent,
+        Some('B') => Priority::High,
+        Some('C') => Priority::Medium,
+        Some(_) => Priority::Low,
+    }
+}
+
+fn priority_letter(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::None => None,
+        Priority::Urgent => Some('A'),
+        Priority::High => Some('B'),
+        Priority::Medium => Some('C'),
+        Priority::Low => Some('D'),
+    }
+}
+
+/// Projects cannot hold spaces, so list names are written with underscores.
+fn project_name(list: &TodoList) -> String {
+    list.name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Writes one todo as a line. The creation date is always written so a title
+/// starting with `x` or a priority is not read back as one, and words of the
+/// title like `+1` are escaped as `\+1`. Completed todos keep their priority
+/// as `pri:`, as todo.txt drops the `(A)` on completion.
+pub fn format_task(todo: &Todo, lists: &BTreeMap<usize, TodoList>) -> String {
+    let mut parts = Vec::new();
+    let priority = priority_letter(todo.priority);
+    if todo.completed {
+        parts.push(String::from("x"));
+        let completed_at = todo.completed_at.unwrap_or(todo.created_at);
+        parts.push(completed_at.format(DATE_FORMAT).to_string());
+    } else if let Some(priority) = priority {
+        parts.push(format!("({})", priority));
+    }
+    parts.push(todo.created_at.format(DATE_FORMAT).to_string());
+    parts.push(escape_title(&todo.title));
+
+    if todo.list_id != INBOX_LIST_ID {
+        if let Some(list) = lists.get(&todo.list_id) {
+            parts.push(format!("+{}", project_name(list)));
+        }
+    }
+    parts.extend(todo.tags.iter().map(|tag| format!("@{}", tag)));
+    if let Some(due) = todo.due {
+        parts.push(format!("due:{}", due.format(DATE_FORMAT)));
+    }
+    if let (true, Some(priority)) = (todo.completed, priority) {
+        parts.push(format!("pri:{}", priority));
+    }
+    parts.push(format!("id:{}", todo.id));
+    parts.join(" ")
+}
+
+/// Writes the todos that are not in the trash, oldest first.
+pub fn write(document: &TodoDocument) -> String {
+    let mut todos: Vec<&Todo> = document.todos.values().filter(|todo| !todo.is_trashed()).collect();
+    todos.sort_by_key(|todo| (todo.created_at, todo.id));
+    write_todos(&todos, &document.lists)
+}
+
+/// Writes `todos` in the given order, one line each.
+pub fn write_todos(todos: &[&Todo], lists: &BTreeMap<usize, TodoList>) -> String {
+    todos
+        .iter()
+        .map(|todo| format_task(todo, lists) + "\n")
+        .collect()
+}
+
+/// Reads a todo.txt file on top of `base`, the document it was last written
+/// from. Lines with the `id:` of a todo in `base` update that todo, so the
+/// fields todo.txt cannot hold, like descriptions and subtasks, are kept.
+/// Todos of `base` without a line were deleted, unless they are in the
+/// trash, which is not written to the file. Projects missing from the lists
+/// are added as lists.
+///
+/// Lines without an id get one derived from their text, so reading the same
+/// file twice gives the same todos until the next save writes the ids.
+pub fn read(data: &str, mut base: TodoDocument, now: DateTime<Local>) -> TodoDocument {
+    let today = now.date_naive();
+    let mut todos = HashMap::new();
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+
+    for line in data.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let task = Task::parse(line);
+        let occurrence = occurrences.entry(line).or_default();
+        *occurrence += 1;
+        let id = task
+            .id
+            .filter(|id| !todos.contains_key(id))
+            .unwrap_or_else(|| TodoId::from_key(&format!("{}\n{}", line, occurrence)));
+
+        let list_id = match &task.project {
+            Some(project) => list_for_project(&mut base, project),
+            None => INBOX_LIST_ID,
+        };
+        let mut todo = base.todos.get(&id).cloned().unwrap_or_else(|| Todo {
+            id,
+            title: String::new(),
+            description: String::new(),
+            completed: false,
+            created_at: start_of_day(task.creation_date.unwrap_or(today)).unwrap_or(now),
+            completed_at: None,
+            updated_at: None,
+            due: None,
+            priority: Priority::None,
+            tags: Default::default(),
+            subtasks: Vec::new(),
+            recurrence: None,
+            list_id,
+            series: None,
+            deleted_at: None,
+            activity: Vec::new(),
+        });
+        apply(&task, &mut todo, list_id, line);
+        todos.insert(id, todo);
+    }
+
+    for (id, todo) in std::mem::take(&mut base.todos) {
+        if todo.is_trashed() && !todos.contains_key(&id) {
+            todos.insert(id, todo);
+        }
+    }
+    base.todos = todos;
+    base
+}
+
+/// Copies what a line holds onto its todo. Dates only carry the day, so a
+/// time already known for that day is kept.
+fn apply(task: &Task, todo: &mut Todo, list_id: usize, line: &str) {
+    let same_day = |time: Option<DateTime<Local>>, date: NaiveDate| {
+        time.filter(|time| time.date_naive() == date)
+    };
+
+    todo.title = if task.title.is_empty() { line.to_string() } else { task.title.clone() };
+    todo.completed = task.completed;
+    todo.completed_at = match (task.completed, task.completion_date) {
+        (false, _) => None,
+        (true, Some(date)) => same_day(todo.completed_at, date).or_else(|| start_of_day(date)),
+        (true, None) => todo.completed_at,
+    };
+    if let Some(date) = task.creation_date {
+        if todo.created_at.date_naive() != date {
+            todo.created_at = start_of_day(date).unwrap_or(todo.created_at);
+        }
+    }
+    todo.due = task
+        .due
+        .and_then(|date| same_day(todo.due, date).or_else(|| end_of_day(date)));
+    todo.priority = priority_from_letter(task.priority);
+    todo.tags = task.contexts.iter().cloned().collect();
+    todo.list_id = list_id;
+    // Being in the file takes it out of the trash
+    todo.deleted_at = None;
+}
+
+/// The list `project` names, matched ignoring case, added if missing.
+fn list_for_project(document: &mut TodoDocument, project: &str) -> usize {
+    let existing = document
+        .lists
+        .values()
+        .find(|list| project_name(list).eq_ignore_ascii_case(project));
+    if let Some(list) = existing {
+        return list.id;
+    }
+    let id = document.next_list_id;
+    document.lists.insert(
+        id,
+        TodoList {
+            id,
+            name: project.replace('_', " "),
+            archived: false,
+        },
+    );
+    document.next_list_id += 1;
+    id
+}
+
+fn start_of_day(date: NaiveDate) -> Option<DateTime<Local>> {
+    date.and_hms_opt(0, 0, 0)?.and_local_timezone(Local).earliest()
+}
@@ -0,0 +1,89 @@
+use iced::{
+    widget::{button, column, container, horizontal_space, row, text},
+    Alignment, Element, Length,
+};
+use std::path::PathBuf;
+
+use crate::error::TodoError;
+use crate::storage;
+use crate::Message;
+use super::style::{ButtonStyle, ContainerStyle};
+
+fn banner_button(label: String, message: Message, style: ButtonStyle) -> Element<'static, Message> {
+    button(text(label).size(14))
+        .on_press(message)
+        .padding([4, 8])
+        .style(iced::theme::Button::Custom(Box::new(style)))
+        .into()
+}
+
+/// Shown when `todos.json` could not be loaded. Saving stays disabled until
+/// the user retries, restores a backup or explicitly overwrites the file.
+pub fn view_load_error<'a>(error: &'a TodoError, backups: &'a [PathBuf]) -> Element<'a, Message> {
+    let mut actions = vec![banner_button(
+        String::from("Retry"),
+        Message::LoadTodos,
+        ButtonStyle::Filter,
+    )];
+    actions.extend(backups.iter().map(|path| {
+        let label = match storage::json::backup_time(path) {
+            Some(taken) => format!("Restore from {}", taken.format("%d %b %Y %H:%M")),
+            None => String::from("Restore from backup"),
+        };
+        banner_button(label, Message::RestoreBackup(path.clone()), ButtonStyle::Filter)
+    }));
+    actions.push(banner_button(
+        String::from("Start over"),
+        Message::OverwriteUnreadable,
+        ButtonStyle::Delete,
+    ));
+
+    let content = column![
+        text("Your todos could not be loaded. Changes are not saved until this is resolved.")
+            .size(16),
+        text(error.to_string()).size(14),
+        row(actions).spacing(5).align_items(Alignment::Center),
+    ]
+    .spacing(5);
+
+    container(content)
+        .width(Length::Fill)
+        .padding(15)
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::ErrorBanner)))
+        .into()
+}
+
+pub fn view_save_error(error: &TodoError) -> Element<'_, Message> {
+    let content = row![
+        text(error.to_string()).size(14),
+        horizontal_space(Length::Fill),
+        banner_button(String::from("Retry"), Message::SaveTodos, ButtonStyle::Filter),
+        banner_button(String::from("Dismiss"), Message::DismissSaveError, ButtonStyle::Filter),
+    ]
+    .spacing(5)
+    .align_items(Alignment::Center);
+
+    container(content)
+        .width(Length::Fill)
+        .padding(10)
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::ErrorBanner)))
+        .into()
+}
+
+/// Shown while another window holds the lock of the workspace.
+pub fn view_read_only() -> Element<'static, Message> {
+    let content = row![
+        text("This workspace is open in another window. It is shown read-only and nothing is saved.")
+            .size(14),
+        horizontal_space(Length::Fill),
+        banner_button(String::from("Try again"), Message::RetryLock, ButtonStyle::Filter),
+    ]
+    .spacing(5)
+    .align_items(Alignment::Center);
+
+    container(content)
+        .width(Length::Fill)
+        .padding(10)
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::WarningBanner)))
+        .into()
+}
\ No newline at end of file
@@ -0,0 +1,137 @@
+use iced::{
+    widget::{button, column, container, horizontal_space, pick_list, row, text, text_input},
+    Alignment, Element, Length,
+};
+
+use crate::csv_io::{CsvPanel, DuplicateAction, ExportScope, Field, ImportRow, ImportWizard};
+use crate::todo::{Message, TodoState};
+use super::style::{ButtonStyle, ContainerStyle, TextInputStyle};
+
+/// How many rows the import preview lists.
+const PREVIEW_ROWS: usize = 8;
+
+fn panel_button(label: &str, message: Option<Message>) -> Element<'_, Message> {
+    button(text(label).size(13))
+        .on_press_maybe(message)
+        .padding([4, 8])
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)))
+        .into()
+}
+
+/// Exports todos to a CSV or todo.txt file or reads one into the import wizard.
+pub fn view_csv_panel<'a>(state: &'a TodoState, panel: &'a CsvPanel) -> Element<'a, Message> {
+    let has_path = !panel.path.trim().is_empty();
+    let path_input = text_input("Path to a .csv or todo.txt file", &panel.path)
+        .on_input(Message::CsvPathChanged)
+        .padding(5)
+        .size(13)
+        .width(Length::Fill)
+        .style(iced::theme::TextInput::Custom(Box::new(TextInputStyle::Default)));
+
+    let mut content = column![
+        text("Import and export (CSV or todo.txt)").size(14),
+        row![
+            path_input,
+            panel_button("Export all", has_path.then_some(Message::ExportCsv(ExportScope::All))),
+            panel_button("Export shown", has_path.then_some(Message::ExportCsv(ExportScope::Filtered))),
+            panel_button("Import…", (has_path && !state.is_read_only()).then_some(Message::ImportCsv)),
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center),
+    ]
+    .spacing(5);
+    if let Some(status) = &panel.status {
+        content = content.push(text(status).size(12));
+    }
+    if let Some(wizard) = &panel.import {
+        content = content.push(view_import_wizard(wizard, state.import_preview()));
+    }
+
+    container(content)
+        .width(Length::Fill)
+        .padding(10)
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::Sidebar)))
+        .into()
+}
+
+/// Maps each column to a field, next to its value in the first row, then
+/// previews the rows as they would be imported.
+fn view_import_wizard(wizard: &ImportWizard, preview: Vec<ImportRow>) -> Element<'_, Message> {
+    let first_row = wizard.table.rows.first();
+    let mut columns = column![text("Columns").size(13)].spacing(3);
+    for (index, header) in wizard.table.headers.iter().enumerate() {
+        let sample = first_row
+            .and_then(|row| row.get(index))
+            .map(|cell| cell.chars().take(40).collect::<String>())
+            .unwrap_or_default();
+        columns = columns.push(
+            row![
+                text(header).size(12).width(Length::Fixed(160.0)),
+                pick_list(&Field::ALL[..], wizard.mapping.get(index).copied(), move |field| {
+                    Message::ImportFieldChanged(index, field)
+                })
+                .text_size(12)
+                .padding(4),
+                text(sample).size(12),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+    }
+
+    let new = preview.iter().filter(|row| row.todo.is_ok() && row.duplicate.is_none()).count();
+    let duplicates = preview.iter().filter(|row| row.todo.is_ok() && row.duplicate.is_some()).count();
+    let invalid = preview.iter().filter(|row| row.todo.is_err()).count();
+    let mut rows = column![text(format!(
+        "{} new, {} duplicates, {} cannot be imported",
+        new, duplicates, invalid
+    ))
+    .size(13)]
+    .spacing(3);
+    for row in preview.iter().take(PREVIEW_ROWS) {
+        let line = match &row.todo {
+            Ok(todo) => {
+                let mut line = format!("Line {}: {}", row.line, todo.title);
+                if let Some(list) = &row.list {
+                    line.push_str(&format!(" · {}", list));
+                }
+                if todo.completed {
+                    line.push_str(" · completed");
+                }
+                if row.duplicate.is_some() {
+                    line.push_str(" · duplicate");
+                }
+                line
+            }
+            Err(error) => format!("Line {}: {}", row.line, error),
+        };
+        rows = rows.push(text(line).size(12));
+    }
+    if preview.len() > PREVIEW_ROWS {
+        rows = rows.push(text(format!("… and {} more", preview.len() - PREVIEW_ROWS)).size(12));
+    }
+
+    let importable = match wizard.duplicates {
+        DuplicateAction::Skip => new,
+        DuplicateAction::Update | DuplicateAction::KeepBoth => new + duplicates,
+    };
+    column![
+        columns,
+        rows,
+        row![
+            pick_list(&DuplicateAction::ALL[..], Some(wizard.duplicates), Message::ImportDuplicatesChanged)
+                .text_size(12)
+                .padding(4),
+            horizontal_space(Length::Fill),
+            panel_button("Cancel", Some(Message::CancelImport)),
+            button(text(format!("Import {} todos", importable)).size(13))
+                .on_press_maybe((importable > 0).then_some(Message::ConfirmImport))
+                .padding([4, 8])
+                .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Add))),
+        ]
+        .spacing(5)
+        .align_items(Alignment::Center),
+    ]
+    .spacing(10)
+    .into()
+}
@@ -1,2 +1,6 @@
+pub mod banner;
+pub mod csv_panel;
 pub mod view;
-pub mod style; 
\ No newline at end of file
+pub mod style;
+pub mod unlock;
+pub mod workspace;
\ No newline at end of file
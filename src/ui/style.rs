@@ -1,10 +1,31 @@
 use iced::{Color, Theme};
 use iced::widget::{button, container, checkbox, text_input};
 
+use crate::todo::Priority;
+
+/// Accent color for a priority level, `None` for todos without a priority.
+pub fn priority_color(priority: Priority) -> Option<Color> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some(Color::from_rgb(0.4, 0.7, 0.9)),
+        Priority::Medium => Some(Color::from_rgb(0.95, 0.75, 0.2)),
+        Priority::High => Some(Color::from_rgb(0.95, 0.5, 0.15)),
+        Priority::Urgent => Some(Color::from_rgb(0.85, 0.15, 0.15)),
+    }
+}
+
 pub enum ContainerStyle {
     MainContainer,
     TodoItem,
     CompletedTodoItem,
+    OverdueTodoItem,
+    DueTodayTodoItem,
+    PriorityBar(Priority),
+    TagChip,
+    Sidebar,
+    ErrorBanner,
+    WarningBanner,
+    Snackbar,
 }
 
 impl container::StyleSheet for ContainerStyle {
@@ -33,6 +54,59 @@ impl container::StyleSheet for ContainerStyle {
                 border_color: Color::from_rgb(0.9, 0.9, 0.9),
                 ..Default::default()
             },
+            ContainerStyle::OverdueTodoItem => container::Appearance {
+                background: Some(Color::from_rgb(1.0, 0.94, 0.94).into()),
+                border_radius: 3.0.into(),
+                border_width: 1.0,
+                border_color: Color::from_rgb(0.85, 0.3, 0.3),
+                ..Default::default()
+            },
+            ContainerStyle::DueTodayTodoItem => container::Appearance {
+                background: Some(Color::from_rgb(1.0, 0.98, 0.9).into()),
+                border_radius: 3.0.into(),
+                border_width: 1.0,
+                border_color: Color::from_rgb(0.9, 0.7, 0.2),
+                ..Default::default()
+            },
+            ContainerStyle::PriorityBar(priority) => container::Appearance {
+                background: priority_color(*priority).map(Into::into),
+                border_radius: 2.0.into(),
+                ..Default::default()
+            },
+            ContainerStyle::TagChip => container::Appearance {
+                background: Some(Color::from_rgb(0.88, 0.93, 1.0).into()),
+                text_color: Some(Color::from_rgb(0.2, 0.35, 0.6)),
+                border_radius: 10.0.into(),
+                border_width: 1.0,
+                border_color: Color::from_rgb(0.7, 0.8, 0.95),
+            },
+            ContainerStyle::ErrorBanner => container::Appearance {
+                background: Some(Color::from_rgb(1.0, 0.92, 0.92).into()),
+                text_color: Some(Color::from_rgb(0.5, 0.1, 0.1)),
+                border_radius: 3.0.into(),
+                border_width: 1.0,
+                border_color: Color::from_rgb(0.85, 0.3, 0.3),
+            },
+            ContainerStyle::WarningBanner => container::Appearance {
+                background: Some(Color::from_rgb(1.0, 0.97, 0.85).into()),
+                text_color: Some(Color::from_rgb(0.4, 0.3, 0.0)),
+                border_radius: 3.0.into(),
+                border_width: 1.0,
+                border_color: Color::from_rgb(0.9, 0.75, 0.3),
+            },
+            ContainerStyle::Snackbar => container::Appearance {
+                background: Some(Color::from_rgb(0.2, 0.2, 0.2).into()),
+                text_color: Some(Color::WHITE),
+                border_radius: 5.0.into(),
+                ..Default::default()
+            },
+            ContainerStyle::Sidebar => container::Appearance {
+                background: Some(Color::from_rgb(0.98, 0.98, 0.98).into()),
+                border_radius: 3.0.into(),
+                border_width: 1.0,
+                border_color: Color::from_rgb(0.9, 0.9, 0.9),
+                ..Default::default()
+            },
         }
     }
 }
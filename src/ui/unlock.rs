@@ -0,0 +1,119 @@
+use iced::{
+    widget::{button, column, container, row, text, text_input},
+    Alignment, Element, Length,
+};
+
+use crate::{Encryption, Message, PassphraseForm};
+use super::style::{ButtonStyle, ContainerStyle, TextInputStyle};
+
+fn passphrase_input<'a>(
+    placeholder: &str,
+    value: &'a str,
+    on_input: fn(String) -> Message,
+    on_submit: Message,
+) -> Element<'a, Message> {
+    text_input(placeholder, value)
+        .password()
+        .on_input(on_input)
+        .on_submit(on_submit)
+        .padding(8)
+        .size(14)
+        .width(Length::Fixed(280.0))
+        .style(iced::theme::TextInput::Custom(Box::new(TextInputStyle::Default)))
+        .into()
+}
+
+fn form_button(label: &str, message: Message, style: ButtonStyle, enabled: bool) -> Element<'_, Message> {
+    button(text(label).size(14))
+        .on_press_maybe(enabled.then_some(message))
+        .padding([5, 10])
+        .style(iced::theme::Button::Custom(Box::new(style)))
+        .into()
+}
+
+fn form_error(form: &PassphraseForm) -> Option<Element<'_, Message>> {
+    form.error.as_ref().map(|error| {
+        text(error)
+            .size(13)
+            .style(iced::theme::Text::Color(iced::Color::from_rgb(0.7, 0.1, 0.1)))
+            .into()
+    })
+}
+
+/// Shown in place of the todos while an encrypted workspace is locked.
+pub fn view_unlock(form: &PassphraseForm) -> Element<'_, Message> {
+    let status = if form.working { "Unlocking…" } else { "" };
+    let mut content = column![
+        text("This workspace is encrypted").size(22),
+        text("Enter its passphrase to show the todos.").size(14),
+        passphrase_input("Passphrase", &form.passphrase, Message::PassphraseChanged, Message::Unlock),
+        form_button("Unlock", Message::Unlock, ButtonStyle::Filter, !form.working),
+        text(status).size(13),
+    ]
+    .spacing(10)
+    .align_items(Alignment::Center);
+    if let Some(error) = form_error(form) {
+        content = content.push(error);
+    }
+
+    container(content)
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_x()
+        .center_y()
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::MainContainer)))
+        .into()
+}
+
+/// Turns encryption of a JSON workspace on or off and changes its passphrase.
+pub fn view_encryption_menu<'a>(
+    encryption: &Encryption,
+    form: &'a PassphraseForm,
+    read_only: bool,
+) -> Element<'a, Message> {
+    let enabled = !form.working;
+    // Rewriting the files is left to the window holding the lock
+    let can_rewrite = enabled && !read_only;
+    let fields = |action: Message| {
+        row![
+            passphrase_input("New passphrase", &form.passphrase, Message::PassphraseChanged, action.clone()),
+            passphrase_input("Repeat passphrase", &form.confirm, Message::PassphraseConfirmChanged, action),
+        ]
+        .spacing(5)
+    };
+
+    let mut content = match encryption {
+        Encryption::Plain => column![
+            text("Encryption").size(14),
+            text("Encrypts the todos, archive and backups with a passphrase. It cannot be recovered if forgotten.")
+                .size(12),
+            fields(Message::EncryptWorkspace),
+            form_button("Encrypt", Message::EncryptWorkspace, ButtonStyle::Filter, can_rewrite),
+        ],
+        Encryption::Unlocked(_) => column![
+            row![
+                text("Encryption is on").size(14),
+                form_button("Lock now", Message::LockWorkspace, ButtonStyle::Filter, enabled),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+            fields(Message::ChangePassphrase),
+            row![
+                form_button("Change passphrase", Message::ChangePassphrase, ButtonStyle::Filter, can_rewrite),
+                form_button("Decrypt to plain JSON", Message::DecryptWorkspace, ButtonStyle::Delete, can_rewrite),
+            ]
+            .spacing(5),
+        ],
+        Encryption::Locked => column![],
+    }
+    .spacing(5);
+    if let Some(error) = form_error(form) {
+        content = content.push(error);
+    }
+
+    container(content)
+        .width(Length::Fill)
+        .padding(10)
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::Sidebar)))
+        .into()
+}
@@ -1,41 +1,64 @@
 use iced::{
     widget::{
-        button, checkbox, column, container, horizontal_rule, horizontal_space, row, scrollable, 
-        text, text_input, vertical_space
+        button, checkbox, column, container, horizontal_rule, horizontal_space, pick_list, row,
+        scrollable, text, text_input, vertical_space
     },
     Alignment, Element, Length,
 };
+use chrono::{DateTime, Datelike, Duration, Local};
 
-use crate::todo::{Filter, Message, Todo, TodoState};
+use crate::merge::{Conflict, Resolution};
+use crate::recurrence::Recurrence;
+use crate::todo::{
+    end_of_day, parse_due, DueStatus, EditTarget, Filter, ListSelection, Message, Notice, Priority, SortOrder,
+    TagMatch, Todo, TodoList, TodoState, INBOX_LIST_ID,
+};
+use super::csv_panel::view_csv_panel;
 use super::style::{ButtonStyle, ContainerStyle, CheckboxStyle, TextInputStyle};
 
-pub fn view_app(state: &TodoState) -> Element<Message> {
+pub fn view_app(state: &TodoState) -> Element<'_, Message> {
     let title = text("Todo App")
         .size(28)
         .width(Length::Fill)
         .horizontal_alignment(iced::alignment::Horizontal::Center);
 
+    let list_switcher = view_list_switcher(state);
     let add_todo_input = view_add_todo_input(state);
     let search_and_filter = view_search_and_filter(state);
+    let tag_sidebar = view_tag_sidebar(state);
     let todo_list = view_todo_list(state);
 
-    container(
-        column![
+    // Conflicts with the file on disk are shown above everything else
+    let mut header = column![title].spacing(10);
+    if !state.conflicts().is_empty() {
+        header = header.push(view_conflicts(state.conflicts()));
+    }
+
+    let mut content = column![
             vertical_space(20),
-            title,
+            header,
             vertical_space(20),
+            list_switcher,
             add_todo_input,
             vertical_space(20),
             search_and_filter,
-            vertical_space(10),
-            horizontal_rule(1),
-            vertical_space(10),
-            todo_list,
         ]
         .spacing(10)
         .padding(20)
-        .align_items(Alignment::Center)
-    )
+        .align_items(Alignment::Center);
+    if let Some(panel) = state.csv_panel() {
+        content = content.push(view_csv_panel(state, panel));
+    }
+    content = content
+        .push(vertical_space(10))
+        .push(horizontal_rule(1))
+        .push(vertical_space(10))
+        .push(row![tag_sidebar, todo_list].spacing(20));
+    if let Some(notice) = state.notice() {
+        content = content.push(view_snackbar(notice));
+    }
+
+    container(content)
     .style(iced::theme::Container::Custom(Box::new(ContainerStyle::MainContainer)))
     .width(Length::Fill)
     .height(Length::Fill)
@@ -43,17 +66,164 @@ pub fn view_app(state: &TodoState) -> Element<Message> {
     .into()
 }
 
-fn view_add_todo_input(state: &TodoState) -> Element<Message> {
+/// Todos changed both in the app and in the file since it was last read.
+/// Until one side is picked the app keeps its own version.
+fn view_conflicts(conflicts: &[Conflict]) -> Element<'_, Message> {
+    let resolve_button = |label: &'static str, message: Message| {
+        button(text(label).size(14))
+            .on_press(message)
+            .padding([4, 8])
+            .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)))
+    };
+    let side = |todo: &Option<Todo>| match todo {
+        Some(todo) if todo.is_trashed() => String::from("in the trash"),
+        Some(todo) if todo.completed => format!("\"{}\", completed", todo.title),
+        Some(todo) => format!("\"{}\", open", todo.title),
+        None => String::from("removed"),
+    };
+
+    let heading = match conflicts.len() {
+        1 => String::from("1 todo was changed both here and in the file on disk"),
+        count => format!("{} todos were changed both here and in the file on disk", count),
+    };
+    let mut content = column![text(heading).size(16)].spacing(8);
+    for conflict in conflicts {
+        content = content.push(
+            row![
+                column![
+                    text(conflict.title()).size(14),
+                    text(format!("Here: {} · On disk: {}", side(&conflict.ours), side(&conflict.theirs)))
+                        .size(12),
+                ]
+                .width(Length::Fill),
+                resolve_button("Keep mine", Message::ResolveConflict(conflict.id, Resolution::KeepMine)),
+                resolve_button("Use theirs", Message::ResolveConflict(conflict.id, Resolution::UseTheirs)),
+            ]
+            .spacing(5)
+            .align_items(Alignment::Center),
+        );
+    }
+    if conflicts.len() > 1 {
+        content = content.push(
+            row![
+                horizontal_space(Length::Fill),
+                resolve_button("Keep all mine", Message::ResolveAllConflicts(Resolution::KeepMine)),
+                resolve_button("Use all theirs", Message::ResolveAllConflicts(Resolution::UseTheirs)),
+            ]
+            .spacing(5),
+        );
+    }
+
+    container(content)
+        .width(Length::Fill)
+        .padding(15)
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::WarningBanner)))
+        .into()
+}
+
+/// Reports what was just done, offering to undo it where possible.
+fn view_snackbar(notice: &Notice) -> Element<'_, Message> {
+    let mut content = row![text(&notice.text).size(14), horizontal_space(Length::Fill)]
+        .spacing(10)
+        .align_items(Alignment::Center);
+    if notice.undoable {
+        content = content.push(
+            button(text("Undo").size(14))
+                .on_press(Message::Undo)
+                .padding([4, 8])
+                .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter))),
+        );
+    }
+    content = content.push(
+        button(text("✕").size(14))
+            .on_press(Message::DismissNotice)
+            .padding([4, 8])
+            .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter))),
+    );
+    container(content)
+    .style(iced::theme::Container::Custom(Box::new(ContainerStyle::Snackbar)))
+    .padding([6, 12])
+    .width(Length::Fixed(400.0))
+    .into()
+}
+
+fn view_list_switcher(state: &TodoState) -> Element<'_, Message> {
+    let selected = state.selected_list();
+    let list_button = |label: String, selection: ListSelection| {
+        button(text(label).size(14))
+            .on_press(Message::SelectList(selection))
+            .padding([5, 10])
+            .style(iced::theme::Button::Custom(Box::new(if selected == selection {
+                ButtonStyle::FilterActive
+            } else {
+                ButtonStyle::Filter
+            })))
+    };
+
+    let mut lists = row![list_button("All lists".to_string(), ListSelection::All)].spacing(5);
+    for list in state.lists() {
+        let (open, total) = state.list_counts(list.id);
+        let label = if list.archived {
+            format!("{} (archived, {})", list.name, total)
+        } else {
+            format!("{} ({}/{})", list.name, open, total)
+        };
+        lists = lists.push(list_button(label, ListSelection::List(list.id)));
+    }
+
+    let new_list = text_input("New list...", state.new_list_name())
+        .on_input(Message::NewListNameChanged)
+        .on_submit(Message::CreateList)
+        .padding(5)
+        .size(14)
+        .width(Length::Fixed(140.0))
+        .style(iced::theme::TextInput::Custom(Box::new(TextInputStyle::Default)));
+
+    let lists = scrollable(lists)
+        .direction(scrollable::Direction::Horizontal(scrollable::Properties::default()))
+        .width(Length::Fill);
+
+    let mut switcher = row![lists, new_list]
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .width(Length::Fill);
+
+    let selected_list = match selected {
+        ListSelection::List(id) if id != INBOX_LIST_ID => {
+            state.lists().find(|list| list.id == id)
+        }
+        _ => None,
+    };
+    if let Some(list) = selected_list {
+        let archive = button(text(if list.archived { "Unarchive" } else { "Archive" }).size(14))
+            .on_press(Message::SetListArchived(list.id, !list.archived))
+            .padding([5, 10])
+            .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)));
+        let delete = button(text("Delete list").size(14))
+            .on_press(Message::DeleteList(list.id))
+            .padding([5, 10])
+            .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Delete)));
+        switcher = switcher.push(archive).push(delete);
+    }
+
+    switcher.into()
+}
+
+fn view_add_todo_input(state: &TodoState) -> Element<'_, Message> {
     let default_string = String::new();
-    let (id, input_value) = state.editing().unwrap_or((0, &default_string));
+    let (target, input_value) = state.editing().unwrap_or((EditTarget::NewTodo, &default_string));
     
-    let input = text_input("Add a new todo...", input_value)
+    let input = text_input("Add a new todo... (use #tags)", input_value)
         .on_input(Message::InputChanged)
         .on_submit(Message::AddTodo)
         .padding(10)
         .width(Length::Fill)
         .style(iced::theme::TextInput::Custom(
-            Box::new(if id == 0 { TextInputStyle::Focused } else { TextInputStyle::Default })
+            Box::new(if target == EditTarget::NewTodo {
+                TextInputStyle::Focused
+            } else {
+                TextInputStyle::Default
+            })
         ));
     
     let add_button = button(text("Add").horizontal_alignment(iced::alignment::Horizontal::Center))
@@ -69,8 +239,8 @@ fn view_add_todo_input(state: &TodoState) -> Element<Message> {
         .into()
 }
 
-fn view_search_and_filter(state: &TodoState) -> Element<Message> {
-    let search_input = text_input("Search todos...", &state.search_query())
+fn view_search_and_filter(state: &TodoState) -> Element<'_, Message> {
+    let search_input = text_input("Search todos... (updated:7d, completed:2w)", state.search_query())
         .on_input(Message::SearchQueryChanged)
         .padding(10)
         .width(Length::Fill)
@@ -109,12 +279,62 @@ fn view_search_and_filter(state: &TodoState) -> Element<Message> {
             }
         )));
 
+    let filter_trash = button(text(format!("Trash ({})", state.trash_count())))
+        .on_press(Message::FilterChanged(Filter::Trash))
+        .padding(10)
+        .style(iced::theme::Button::Custom(Box::new(
+            if *state.filter() == Filter::Trash {
+                ButtonStyle::FilterActive
+            } else {
+                ButtonStyle::Filter
+            }
+        )));
+
+    let filter_archive = button(text("Archive"))
+        .on_press(Message::FilterChanged(Filter::Archive))
+        .padding(10)
+        .style(iced::theme::Button::Custom(Box::new(
+            if *state.filter() == Filter::Archive {
+                ButtonStyle::FilterActive
+            } else {
+                ButtonStyle::Filter
+            }
+        )));
+
+    let mut archive_completed = button(text("Archive completed"))
+        .padding(10)
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)));
+    if state.has_completed() {
+        archive_completed = archive_completed.on_press(Message::ArchiveCompleted);
+    }
+
+    let sort = pick_list(
+        &SortOrder::ALL[..],
+        Some(state.sort_order()),
+        Message::SortChanged,
+    )
+    .padding(10);
+
+    let csv = button(text("CSV"))
+        .on_press(Message::ToggleCsvPanel)
+        .padding(10)
+        .style(iced::theme::Button::Custom(Box::new(if state.csv_panel().is_some() {
+            ButtonStyle::FilterActive
+        } else {
+            ButtonStyle::Filter
+        })));
+
     row![
         search_input,
         horizontal_space(10),
         filter_all,
         filter_active,
-        filter_completed
+        filter_completed,
+        filter_trash,
+        filter_archive,
+        sort,
+        archive_completed,
+        csv
     ]
     .spacing(5)
     .align_items(Alignment::Center)
@@ -122,12 +342,92 @@ fn view_search_and_filter(state: &TodoState) -> Element<Message> {
     .into()
 }
 
-fn view_todo_list(state: &TodoState) -> Element<Message> {
+fn view_tag_sidebar(state: &TodoState) -> Element<'_, Message> {
+    let tags = state.all_tags();
+    if tags.is_empty() {
+        return column![].into();
+    }
+
+    let selected = state.selected_tags();
+    let filter_button = |label: String, active: bool, message: Message| {
+        button(text(label).size(14))
+            .on_press(message)
+            .padding([4, 8])
+            .width(Length::Fill)
+            .style(iced::theme::Button::Custom(Box::new(if active {
+                ButtonStyle::FilterActive
+            } else {
+                ButtonStyle::Filter
+            })))
+    };
+
+    let mut tag_buttons = column![text("Tags").size(16)].spacing(5);
+    for (tag, count) in tags {
+        tag_buttons = tag_buttons.push(filter_button(
+            format!("#{} ({})", tag, count),
+            selected.contains(tag),
+            Message::ToggleTagFilter(tag.to_string()),
+        ));
+    }
+
+    let tag_match = row![
+        filter_button(
+            "Any".to_string(),
+            state.tag_match() == TagMatch::Any,
+            Message::TagMatchChanged(TagMatch::Any),
+        ),
+        filter_button(
+            "All".to_string(),
+            state.tag_match() == TagMatch::All,
+            Message::TagMatchChanged(TagMatch::All),
+        ),
+    ]
+    .spacing(5);
+
+    let clear = filter_button("Clear".to_string(), false, Message::ClearTagFilter);
+
+    container(
+        scrollable(column![tag_buttons, tag_match, clear].spacing(10))
+            .height(Length::Fill),
+    )
+    .width(Length::Fixed(160.0))
+    .padding(10)
+    .style(iced::theme::Container::Custom(Box::new(ContainerStyle::Sidebar)))
+    .into()
+}
+
+fn view_tag_chips(todo: &Todo) -> Element<'_, Message> {
+    let chips: Vec<Element<_>> = todo
+        .tags
+        .iter()
+        .map(|tag| {
+            let remove = button(text("×").size(12))
+                .on_press(Message::RemoveTag(todo.id, tag.clone()))
+                .padding([0, 4])
+                .style(iced::theme::Button::Text);
+
+            container(row![text(format!("#{}", tag)).size(12), remove].align_items(Alignment::Center))
+                .padding([2, 8])
+                .style(iced::theme::Container::Custom(Box::new(ContainerStyle::TagChip)))
+                .into()
+        })
+        .collect();
+
+    row(chips).spacing(5).into()
+}
+
+fn view_todo_list(state: &TodoState) -> Element<'_, Message> {
+    match state.filter() {
+        Filter::Trash => return view_trash(state),
+        Filter::Archive => return view_archive(state),
+        _ => {}
+    }
+
     let todos = state.filtered_todos();
     
     let items: Vec<Element<_>> = todos
         .iter()
-        .map(|todo| view_todo_item(todo))
+        .map(|todo| view_todo_item(state, todo))
         .collect();
 
     if items.is_empty() {
@@ -151,7 +451,199 @@ fn view_todo_list(state: &TodoState) -> Element<Message> {
     }
 }
 
-fn view_todo_item(todo: &Todo) -> Element<Message> {
+/// Number of days deleted todos are kept, 0 keeps them forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RetentionChoice(u32);
+
+impl RetentionChoice {
+    const ALL: [RetentionChoice; 4] = [
+        RetentionChoice(7),
+        RetentionChoice(30),
+        RetentionChoice(90),
+        RetentionChoice(0),
+    ];
+}
+
+impl std::fmt::Display for RetentionChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0 => f.write_str("Keep forever"),
+            days => write!(f, "Keep {} days", days),
+        }
+    }
+}
+
+fn view_trash(state: &TodoState) -> Element<'_, Message> {
+    let retention = pick_list(
+        &RetentionChoice::ALL[..],
+        Some(RetentionChoice(state.trash_retention_days())),
+        |choice| Message::TrashRetentionChanged(choice.0),
+    )
+    .text_size(14)
+    .padding(5);
+
+    let mut empty_trash = button(text("Empty trash").size(14))
+        .padding([5, 10])
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Delete)));
+    if state.trash_count() > 0 {
+        empty_trash = empty_trash.on_press(Message::EmptyTrash);
+    }
+
+    let toolbar = row![horizontal_space(Length::Fill), retention, empty_trash]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+    let items: Vec<Element<_>> = state
+        .filtered_todos()
+        .into_iter()
+        .map(view_trashed_item)
+        .collect();
+
+    let list: Element<_> = if items.is_empty() {
+        text("The trash is empty.")
+            .width(Length::Fill)
+            .horizontal_alignment(iced::alignment::Horizontal::Center)
+            .into()
+    } else {
+        scrollable(column(items).spacing(10).width(Length::Fill))
+            .height(Length::Fill)
+            .width(Length::Fill)
+            .into()
+    };
+
+    column![toolbar, list].spacing(10).width(Length::Fill).into()
+}
+
+/// Days after completion when todos are archived, 0 never archives them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AutoArchiveChoice(u32);
+
+impl AutoArchiveChoice {
+    const ALL: [AutoArchiveChoice; 4] = [
+        AutoArchiveChoice(0),
+        AutoArchiveChoice(7),
+        AutoArchiveChoice(30),
+        AutoArchiveChoice(90),
+    ];
+}
+
+impl std::fmt::Display for AutoArchiveChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0 => f.write_str("Archive manually"),
+            days => write!(f, "Archive after {} days", days),
+        }
+    }
+}
+
+fn view_archive(state: &TodoState) -> Element<'_, Message> {
+    let auto_archive = pick_list(
+        &AutoArchiveChoice::ALL[..],
+        Some(AutoArchiveChoice(state.auto_archive_days())),
+        |choice| Message::AutoArchiveChanged(choice.0),
+    )
+    .text_size(14)
+    .padding(5);
+
+    let toolbar = row![horizontal_space(Length::Fill), auto_archive]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+    let items: Vec<Element<_>> = state
+        .filtered_todos()
+        .into_iter()
+        .map(view_archived_item)
+        .collect();
+
+    let status = match state.archive_error() {
+        Some(error) => Some(format!("The archive could not be loaded: {}", error)),
+        None if !state.archive_loaded() => Some(String::from("Loading the archive...")),
+        None if items.is_empty() => Some(String::from("No archived todos.")),
+        None => None,
+    };
+
+    let list: Element<_> = match status {
+        Some(status) => text(status)
+            .width(Length::Fill)
+            .horizontal_alignment(iced::alignment::Horizontal::Center)
+            .into(),
+        None => scrollable(column(items).spacing(10).width(Length::Fill))
+            .height(Length::Fill)
+            .width(Length::Fill)
+            .into(),
+    };
+
+    column![toolbar, list].spacing(10).width(Length::Fill).into()
+}
+
+fn view_archived_item(todo: &Todo) -> Element<'_, Message> {
+    let completed = todo
+        .completed_at
+        .map(|completed_at| format!("Completed {}", completed_at.format("%d %b %Y")))
+        .unwrap_or_default();
+
+    let details = column![
+        text(&todo.title).size(18),
+        text(completed)
+            .size(12)
+            .style(iced::theme::Text::Color(iced::Color::from_rgb(0.5, 0.5, 0.5))),
+    ]
+    .spacing(5)
+    .width(Length::Fill);
+
+    let restore_button = button(text("Restore"))
+        .on_press(Message::UnarchiveTodo(todo.id))
+        .padding(5)
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)));
+
+    container(
+        row![details, view_tag_chips(todo), restore_button]
+            .spacing(20)
+            .align_items(Alignment::Center),
+    )
+    .width(Length::Fill)
+    .padding(15)
+    .style(iced::theme::Container::Custom(Box::new(ContainerStyle::CompletedTodoItem)))
+    .into()
+}
+
+fn view_trashed_item(todo: &Todo) -> Element<'_, Message> {
+    let deleted = todo
+        .deleted_at
+        .map(|deleted_at| format!("Deleted {}", deleted_at.format("%d %b %Y %H:%M")))
+        .unwrap_or_default();
+
+    let details = column![
+        text(&todo.title).size(18),
+        text(deleted)
+            .size(12)
+            .style(iced::theme::Text::Color(iced::Color::from_rgb(0.5, 0.5, 0.5))),
+    ]
+    .spacing(5)
+    .width(Length::Fill);
+
+    let restore_button = button(text("Restore"))
+        .on_press(Message::RestoreTodo(todo.id))
+        .padding(5)
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)));
+
+    let delete_button = button(text("Delete forever"))
+        .on_press(Message::DeletePermanently(todo.id))
+        .padding(5)
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Delete)));
+
+    container(
+        row![details, restore_button, delete_button]
+            .spacing(20)
+            .align_items(Alignment::Center),
+    )
+    .width(Length::Fill)
+    .padding(15)
+    .style(iced::theme::Container::Custom(Box::new(ContainerStyle::CompletedTodoItem)))
+    .into()
+}
+
+fn view_todo_item<'a>(state: &'a TodoState, todo: &'a Todo) -> Element<'a, Message> {
     let checkbox = checkbox(
         "",
         todo.completed,
@@ -162,6 +654,7 @@ fn view_todo_item(todo: &Todo) -> Element<Message> {
     let title = text(&todo.title)
         .width(Length::Fill)
         .size(18);
+    let expanded = state.is_expanded(todo.id);
     
     let title = if todo.completed {
         title.style(iced::theme::Text::Color(iced::Color::from_rgb(0.5, 0.5, 0.5)))
@@ -169,6 +662,17 @@ fn view_todo_item(todo: &Todo) -> Element<Message> {
         title
     };
 
+    let now = Local::now();
+    let timestamp = match todo.completed_at {
+        Some(completed_at) if todo.completed => {
+            format!("Completed {}", relative_time(completed_at, now))
+        }
+        _ => format!("Updated {}", relative_time(todo.last_updated(), now)),
+    };
+    let timestamp = text(timestamp)
+        .size(12)
+        .style(iced::theme::Text::Color(iced::Color::from_rgb(0.6, 0.6, 0.6)));
+
     let description = if !todo.description.is_empty() {
         text(&todo.description)
             .size(14)
@@ -177,6 +681,9 @@ fn view_todo_item(todo: &Todo) -> Element<Message> {
         text("")
     };
 
+    let due_status = todo.due_status(now);
+    let due_editor = view_due_editor(state, todo, due_status);
+
     let delete_button = button(text("Delete").horizontal_alignment(iced::alignment::Horizontal::Center))
         .on_press(Message::DeleteTodo(todo.id))
         .padding(5)
@@ -187,9 +694,59 @@ fn view_todo_item(todo: &Todo) -> Element<Message> {
         .padding(5)
         .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)));
 
+    let priority = pick_list(
+        &Priority::ALL[..],
+        Some(todo.priority),
+        move |priority| Message::PriorityChanged(todo.id, priority),
+    )
+    .text_size(14)
+    .padding(5);
+
+    let move_to = view_move_picker(state, todo);
+
+    let priority_bar = container(text(""))
+        .width(Length::Fixed(4.0))
+        .height(Length::Fixed(40.0))
+        .style(iced::theme::Container::Custom(Box::new(ContainerStyle::PriorityBar(
+            todo.priority,
+        ))));
+
+    let (done, total) = todo.subtask_progress();
+    let expand_button = button(
+        text(if total > 0 {
+            format!("{} {}/{} done", if expanded { "▾" } else { "▸" }, done, total)
+        } else if expanded {
+            "▾ Subtasks".to_string()
+        } else {
+            "▸ Subtasks".to_string()
+        })
+        .size(12),
+    )
+    .on_press(Message::ToggleExpanded(todo.id))
+    .padding([2, 6])
+    .style(iced::theme::Button::Text);
+
+    let mut details = column![
+        row![title, expand_button].align_items(Alignment::Center),
+        description,
+        view_tag_chips(todo),
+        due_editor,
+        timestamp
+    ]
+    .spacing(5)
+    .width(Length::Fill);
+    if expanded {
+        details = details
+            .push(view_subtasks(state, todo))
+            .push(view_activity(state, todo));
+    }
+
     let row = row![
+        priority_bar,
         checkbox,
-        column![title, description].spacing(5).width(Length::Fill),
+        details,
+        priority,
+        move_to,
         edit_button,
         delete_button
     ]
@@ -201,11 +758,266 @@ fn view_todo_item(todo: &Todo) -> Element<Message> {
         .width(Length::Fill)
         .padding(15)
         .style(iced::theme::Container::Custom(Box::new(
-            if todo.completed {
-                ContainerStyle::CompletedTodoItem
-            } else {
-                ContainerStyle::TodoItem
+            match due_status {
+                _ if todo.completed => ContainerStyle::CompletedTodoItem,
+                Some(DueStatus::Overdue) => ContainerStyle::OverdueTodoItem,
+                Some(DueStatus::DueToday) => ContainerStyle::DueTodayTodoItem,
+                _ => ContainerStyle::TodoItem,
             }
         )))
         .into()
+}
+
+/// Describes how long ago `time` was, e.g. "2 days ago".
+fn relative_time(time: DateTime<Local>, now: DateTime<Local>) -> String {
+    let elapsed = now - time;
+    let plural = |count: i64, unit: &str| {
+        format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+    };
+
+    if elapsed < Duration::minutes(1) {
+        String::from("just now")
+    } else if elapsed < Duration::hours(1) {
+        plural(elapsed.num_minutes(), "minute")
+    } else if elapsed < Duration::days(1) {
+        plural(elapsed.num_hours(), "hour")
+    } else if elapsed < Duration::days(2) {
+        String::from("yesterday")
+    } else if elapsed < Duration::days(30) {
+        plural(elapsed.num_days(), "day")
+    } else {
+        format!("on {}", time.format("%d %b %Y"))
+    }
+}
+
+/// Entry of the move picker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ListChoice {
+    id: usize,
+    name: String,
+}
+
+impl From<&TodoList> for ListChoice {
+    fn from(list: &TodoList) -> Self {
+        Self {
+            id: list.id,
+            name: list.name.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for ListChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+fn view_move_picker<'a>(state: &'a TodoState, todo: &'a Todo) -> Element<'a, Message> {
+    let choices: Vec<ListChoice> = state.lists().map(ListChoice::from).collect();
+    if choices.len() < 2 {
+        return column![].into();
+    }
+
+    let current = choices.iter().find(|choice| choice.id == todo.list_id).cloned();
+    let id = todo.id;
+    pick_list(choices, current, move |choice| Message::MoveTodo(id, choice.id))
+        .text_size(14)
+        .padding(5)
+        .into()
+}
+
+fn view_subtasks<'a>(state: &'a TodoState, todo: &'a Todo) -> Element<'a, Message> {
+    let items: Vec<Element<_>> = todo
+        .subtasks
+        .iter()
+        .map(|subtask| {
+            let title = text(&subtask.title).size(14).width(Length::Fill);
+            let title = if subtask.completed {
+                title.style(iced::theme::Text::Color(iced::Color::from_rgb(0.5, 0.5, 0.5)))
+            } else {
+                title
+            };
+
+            row![
+                checkbox("", subtask.completed, move |_| {
+                    Message::ToggleSubtask(todo.id, subtask.id)
+                })
+                .size(16)
+                .style(iced::theme::Checkbox::Custom(Box::new(CheckboxStyle::Todo))),
+                title,
+                button(text("×").size(14))
+                    .on_press(Message::DeleteSubtask(todo.id, subtask.id))
+                    .padding([0, 6])
+                    .style(iced::theme::Button::Text),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .into()
+        })
+        .collect();
+
+    let input = text_input("Add a subtask...", state.subtask_input(todo.id))
+        .on_input(move |text| Message::SubtaskInputChanged(todo.id, text))
+        .on_submit(Message::AddSubtask(todo.id))
+        .padding(5)
+        .size(14)
+        .style(iced::theme::TextInput::Custom(Box::new(TextInputStyle::Default)));
+
+    column(items)
+        .push(input)
+        .spacing(5)
+        .padding([5, 0, 0, 20])
+        .into()
+}
+
+/// Collapsible history of a todo, newest first.
+fn view_activity<'a>(state: &'a TodoState, todo: &'a Todo) -> Element<'a, Message> {
+    let open = state.is_activity_open(todo.id);
+    let toggle = button(
+        text(format!("{} History ({})", if open { "▾" } else { "▸" }, todo.activity.len())).size(12),
+    )
+    .on_press(Message::ToggleActivity(todo.id))
+    .padding([2, 6])
+    .style(iced::theme::Button::Text);
+
+    let mut header = row![toggle].spacing(10).align_items(Alignment::Center);
+    if !open {
+        return column![header].padding([5, 0, 0, 20]).into();
+    }
+
+    header = header.push(
+        button(text("Copy as JSON").size(12))
+            .on_press(Message::ExportActivity(todo.id))
+            .padding([2, 6])
+            .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter))),
+    );
+
+    let events = todo.activity.iter().rev().map(|event| {
+        row![
+            text(event.at.format("%d %b %Y %H:%M").to_string())
+                .size(12)
+                .width(Length::Fixed(120.0))
+                .style(iced::theme::Text::Color(iced::Color::from_rgb(0.5, 0.5, 0.5))),
+            text(event.change.to_string()).size(12),
+        ]
+        .spacing(10)
+        .into()
+    });
+
+    column![header, column(events.collect()).spacing(3).padding([0, 0, 0, 10])]
+        .spacing(5)
+        .padding([5, 0, 0, 20])
+        .into()
+}
+
+/// A field to type the due date into, with buttons to nudge it.
+fn view_due_editor<'a>(
+    state: &'a TodoState,
+    todo: &'a Todo,
+    due_status: Option<DueStatus>,
+) -> Element<'a, Message> {
+    let small_button = |label: &'static str, message: Message| {
+        button(text(label).size(12))
+            .on_press(message)
+            .padding([2, 6])
+            .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)))
+    };
+
+    let input = state.due_input(todo.id);
+    let mut date_input = row![text_input("YYYY-MM-DD HH:MM", input)
+        .on_input(move |text| Message::DueInputChanged(todo.id, text))
+        .on_submit(Message::SubmitDueDate(todo.id))
+        .padding(2)
+        .size(12)
+        .width(Length::Fixed(130.0))
+        .style(iced::theme::TextInput::Custom(Box::new(TextInputStyle::Default)))]
+    .spacing(5)
+    .align_items(Alignment::Center);
+    if !input.trim().is_empty() && parse_due(input).is_none() {
+        date_input = date_input.push(
+            text("Not a date")
+                .size(12)
+                .style(iced::theme::Text::Color(iced::Color::from_rgb(0.8, 0.2, 0.2))),
+        );
+    }
+
+    let repeat = view_repeat_picker(todo);
+
+    let Some(due) = todo.due else {
+        let today = end_of_day(Local::now().date_naive());
+        let mut set_due = row![].spacing(5).align_items(Alignment::Center);
+        if let Some(due) = today {
+            set_due = set_due.push(small_button("Today", Message::SetDueDate(todo.id, due)));
+        }
+        return set_due.push(date_input).push(repeat).into();
+    };
+
+    let label = match due_status {
+        Some(DueStatus::Overdue) => text("Overdue")
+            .size(12)
+            .style(iced::theme::Text::Color(iced::Color::from_rgb(0.8, 0.2, 0.2))),
+        Some(DueStatus::DueToday) => text("Due today")
+            .size(12)
+            .style(iced::theme::Text::Color(iced::Color::from_rgb(0.75, 0.5, 0.0))),
+        _ => text("Due")
+            .size(12)
+            .style(iced::theme::Text::Color(iced::Color::from_rgb(0.5, 0.5, 0.5))),
+    };
+
+    row![
+        label,
+        small_button("-1d", Message::ShiftDueDate(todo.id, Duration::days(-1))),
+        text(due.format("%a, %d %b %Y %H:%M").to_string()).size(12),
+        small_button("+1d", Message::ShiftDueDate(todo.id, Duration::days(1))),
+        small_button("-1h", Message::ShiftDueDate(todo.id, Duration::hours(-1))),
+        small_button("+1h", Message::ShiftDueDate(todo.id, Duration::hours(1))),
+        small_button("Clear", Message::ClearDueDate(todo.id)),
+        date_input,
+        repeat,
+    ]
+    .spacing(5)
+    .align_items(Alignment::Center)
+    .into()
+}
+
+/// Entry of the repeat picker, `None` meaning the todo does not recur.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RepeatChoice(Option<Recurrence>);
+
+impl std::fmt::Display for RepeatChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(recurrence) => recurrence.fmt(f),
+            None => f.write_str("Does not repeat"),
+        }
+    }
+}
+
+fn view_repeat_picker(todo: &Todo) -> Element<'_, Message> {
+    let anchor = todo.due.unwrap_or_else(Local::now);
+    let mut choices = vec![
+        RepeatChoice(None),
+        RepeatChoice(Some(Recurrence::Daily)),
+        RepeatChoice(Some(Recurrence::weekdays())),
+        RepeatChoice(Some(Recurrence::Weekly(vec![anchor.weekday()]))),
+        RepeatChoice(Some(Recurrence::MonthlyOnDay(anchor.day()))),
+    ];
+    choices.extend(
+        [1, 3, 7, 14, 30]
+            .into_iter()
+            .map(|days| RepeatChoice(Some(Recurrence::AfterCompletion(days)))),
+    );
+
+    let current = RepeatChoice(todo.recurrence.clone());
+    if !choices.contains(&current) {
+        choices.push(current.clone());
+    }
+
+    let id = todo.id;
+    pick_list(choices, Some(current), move |choice| {
+        Message::RecurrenceChanged(id, choice.0)
+    })
+    .text_size(12)
+    .padding([2, 6])
+    .into()
 } 
\ No newline at end of file
@@ -0,0 +1,77 @@
+use iced::{
+    widget::{button, column, container, horizontal_space, row, text, text_input},
+    Alignment, Element, Length,
+};
+use std::path::{Path, PathBuf};
+
+use crate::Message;
+use super::style::{ButtonStyle, ContainerStyle, TextInputStyle};
+
+fn menu_button(label: String, message: Message) -> Element<'static, Message> {
+    button(text(label).size(13))
+        .on_press(message)
+        .padding([4, 8])
+        .style(iced::theme::Button::Custom(Box::new(ButtonStyle::Filter)))
+        .into()
+}
+
+/// Names the open data file and, when `menu` holds the path being typed,
+/// offers to open another one or one of the recent workspaces.
+pub fn view_workspace_bar<'a>(
+    current: &'a Path,
+    menu: Option<&'a str>,
+    recent: &'a [PathBuf],
+) -> Element<'a, Message> {
+    let bar = row![
+        text(format!("Workspace: {}", current.display())).size(13),
+        horizontal_space(Length::Fill),
+        menu_button(String::from("Workspaces…"), Message::ToggleWorkspaceMenu),
+    ]
+    .spacing(5)
+    .align_items(Alignment::Center);
+
+    let Some(input) = menu else {
+        return container(bar).width(Length::Fill).padding([5, 20]).into();
+    };
+
+    let open = Message::OpenWorkspace(PathBuf::from(input.trim()));
+    let path_input = text_input("Path to a .json, .db, .jsonl or todo.txt file", input)
+        .on_input(Message::WorkspacePathChanged)
+        .on_submit(open.clone())
+        .padding(5)
+        .size(13)
+        .width(Length::Fill)
+        .style(iced::theme::TextInput::Custom(Box::new(TextInputStyle::Default)));
+    let mut menu = column![
+        text("Open workspace…").size(14),
+        row![path_input, menu_button(String::from("Open"), open)]
+            .spacing(5)
+            .align_items(Alignment::Center),
+    ]
+    .spacing(5);
+
+    let others: Vec<&PathBuf> = recent.iter().filter(|path| path.as_path() != current).collect();
+    if !others.is_empty() {
+        menu = menu.push(text("Recent workspaces").size(14));
+        for path in others {
+            menu = menu.push(menu_button(
+                path.display().to_string(),
+                Message::OpenWorkspace(path.clone()),
+            ));
+        }
+    }
+
+    container(
+        column![
+            bar,
+            container(menu)
+                .width(Length::Fill)
+                .padding(10)
+                .style(iced::theme::Container::Custom(Box::new(ContainerStyle::Sidebar))),
+        ]
+        .spacing(5),
+    )
+    .width(Length::Fill)
+    .padding([5, 20])
+    .into()
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use todo_gui::activity::Change;
+use todo_gui::todo::{Message, Priority, TodoId, TodoState};
+use common::add_todo;
+
+fn changes(state: &TodoState, id: TodoId) -> Vec<Change> {
+    state.document().todos[&id]
+        .activity
+        .iter()
+        .map(|event| event.change.clone())
+        .collect()
+}
+
+#[test]
+fn records_renames_reopening_and_priority() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Draft");
+
+    state.update(Message::EditingTodo(id, String::from("Draft")));
+    state.update(Message::InputChanged(String::from("Final")));
+    state.update(Message::FinishEditing);
+    state.update(Message::ToggleTodo(id));
+    state.update(Message::ToggleTodo(id));
+    state.update(Message::PriorityChanged(id, Priority::High));
+
+    assert_eq!(
+        changes(&state, id),
+        [
+            Change::Created,
+            Change::Renamed {
+                from: String::from("Draft"),
+                to: String::from("Final"),
+            },
+            Change::Completed,
+            Change::Reopened,
+            Change::PriorityChanged {
+                from: Priority::None,
+                to: Priority::High,
+            },
+        ]
+    );
+}
+
+#[test]
+fn typing_a_description_is_logged_once() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Report");
+
+    for description in ["D", "Dr", "Draft"] {
+        state.update(Message::DescriptionChanged(id, description.to_string()));
+    }
+    // Logged when typing ends, here by saving after a pause
+    assert_eq!(changes(&state, id), [Change::Created]);
+    state.take_changes();
+
+    assert_eq!(changes(&state, id), [Change::Created, Change::DescriptionChanged]);
+}
+
+#[test]
+fn exports_history_as_json() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Report");
+    state.update(Message::DeleteTodo(id));
+
+    let json: serde_json::Value = serde_json::from_str(&state.activity_json(id).unwrap()).unwrap();
+    assert_eq!(json["title"], "Report");
+    assert_eq!(json["activity"][0]["type"], "created");
+    assert_eq!(json["activity"][1]["type"], "trashed");
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use chrono::{Duration, Local};
+use std::fs;
+use todo_gui::storage::{JsonFileStorage, SqliteStorage, Storage};
+use todo_gui::todo::{Filter, Message, TodoState};
+use common::{add_todo, temp_dir};
+
+/// Archives a completed todo, reloads it from the archive and restores it.
+fn archive_round_trip(storage: &dyn Storage) {
+    let mut state = TodoState::default();
+    let done = add_todo(&mut state, "Done");
+    let open = add_todo(&mut state, "Open");
+    state.update(Message::ToggleTodo(done));
+    let changes = state.take_changes();
+    storage.save_changes(state.document(), &changes).unwrap();
+
+    state.update(Message::ArchiveCompleted);
+    // The history is cleared, so the notice offers no undo
+    assert!(!state.notice().unwrap().undoable);
+    let changes = state.take_changes();
+    assert_eq!(changes.archived.len(), 1);
+    storage.save_changes(state.document(), &changes).unwrap();
+
+    let document = storage.load().unwrap().unwrap();
+    assert!(!document.todos.contains_key(&done));
+    assert!(document.todos.contains_key(&open));
+
+    state.update(Message::FilterChanged(Filter::Archive));
+    state.set_archive(storage.load_archive());
+    assert_eq!(state.filtered_todos()[0].title, "Done");
+
+    state.update(Message::UnarchiveTodo(done));
+    let changes = state.take_changes();
+    storage.save_changes(state.document(), &changes).unwrap();
+    assert!(storage.load().unwrap().unwrap().todos[&done].completed);
+    assert!(storage.load_archive().unwrap().is_empty());
+}
+
+#[test]
+fn json_storage_archives_into_a_separate_file() {
+    let dir = temp_dir("json");
+    archive_round_trip(&JsonFileStorage::new(dir.join("todos.json")));
+    assert!(dir.join("todos-archive.json").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sqlite_storage_archives_into_a_separate_table() {
+    let dir = temp_dir("sqlite");
+    archive_round_trip(&SqliteStorage::new(dir.join("todos.db")));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn auto_archives_todos_completed_long_ago() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Done");
+    state.update(Message::ToggleTodo(id));
+    assert!(state.document().todos[&id].completed_at.is_some());
+
+    state.auto_archive(Local::now() + Duration::days(60));
+    assert!(state.document().todos.contains_key(&id));
+
+    state.update(Message::AutoArchiveChanged(30));
+    state.auto_archive(Local::now() + Duration::days(10));
+    assert!(state.document().todos.contains_key(&id));
+    state.auto_archive(Local::now() + Duration::days(60));
+    assert!(!state.document().todos.contains_key(&id));
+    assert!(!state.can_undo());
+}
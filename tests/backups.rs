@@ -0,0 +1,84 @@
+mod common;
+
+use std::fs;
+use todo_gui::storage::json::{backup_time, create_backup, list_backups};
+use todo_gui::storage::{ChangeSet, JsonFileStorage, Storage};
+use todo_gui::todo::TodoState;
+use common::{add_todo, temp_dir};
+
+fn names(backups: &[std::path::PathBuf]) -> Vec<String> {
+    backups
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect()
+}
+
+#[test]
+fn backups_rotate_and_are_taken_at_most_hourly() {
+    let dir = temp_dir("backup_rotation");
+    let path = dir.join("todos.json");
+    fs::write(&path, "{}").unwrap();
+    let backups = dir.join("backups");
+    fs::create_dir_all(&backups).unwrap();
+    for name in [
+        "todos-20240101-120000.json",
+        "todos-20240301-120000.json",
+        "todos-20240201-120000.json",
+        // Not backups of `todos.json`
+        "todos-notes.json",
+        "todos-20241301-120000.json",
+        "other-20240101-120000.json",
+    ] {
+        fs::write(backups.join(name), "{}").unwrap();
+    }
+    assert_eq!(
+        names(&list_backups(&path)),
+        ["todos-20240301-120000.json", "todos-20240201-120000.json", "todos-20240101-120000.json"]
+    );
+
+    // Turned off
+    create_backup(&path, 0).unwrap();
+    assert_eq!(list_backups(&path).len(), 3);
+
+    create_backup(&path, 3).unwrap();
+    let kept = list_backups(&path);
+    assert_eq!(kept.len(), 3);
+    assert!(backup_time(&kept[0]).unwrap().date() > backup_time(&kept[1]).unwrap().date());
+    assert_eq!(names(&kept[1..]), ["todos-20240301-120000.json", "todos-20240201-120000.json"]);
+    assert!(backups.join("todos-notes.json").exists());
+    assert!(backups.join("other-20240101-120000.json").exists());
+
+    // The newest backup is less than an hour old
+    create_backup(&path, 3).unwrap();
+    assert_eq!(list_backups(&path), kept);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn a_backup_restores_the_document_it_was_taken_of() {
+    let dir = temp_dir("backup_restore");
+    let storage = JsonFileStorage::new(dir.join("todos.json"));
+    let mut state = TodoState::default();
+    add_todo(&mut state, "Milk");
+    storage.save(state.document()).unwrap();
+    let saved = state.document().clone();
+    assert!(storage.backups().is_empty());
+
+    add_todo(&mut state, "Bread");
+    storage.save(state.document()).unwrap();
+    let backups = storage.backups();
+    assert_eq!(backups.len(), 1);
+
+    // The file is damaged, the backup still reads
+    fs::write(dir.join("todos.json"), "{\"todos\": ").unwrap();
+    assert!(storage.load().is_err());
+    let restored = storage.read_backup(&backups[0]).unwrap();
+    assert_eq!(restored, saved);
+
+    state.set_document(restored);
+    storage.save_changes(state.document(), &ChangeSet::full()).unwrap();
+    assert_eq!(storage.load().unwrap().unwrap(), saved);
+
+    fs::remove_dir_all(dir).unwrap();
+}
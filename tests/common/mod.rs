@@ -0,0 +1,23 @@
+//! Fixtures shared by the integration tests, each of which only uses some.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use todo_gui::todo::{Message, TodoId, TodoState};
+
+/// Adds a todo through the input, like the user would, and returns its id.
+/// Titles with a `#tag` are split into title and tags, so cannot be found.
+pub fn add_todo(state: &mut TodoState, title: &str) -> TodoId {
+    state.update(Message::AddTodo);
+    state.update(Message::InputChanged(title.to_string()));
+    state.update(Message::AddTodo);
+    state.document().todos.values().find(|todo| todo.title == title).unwrap().id
+}
+
+/// An empty directory for the test, removed by it when done.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("todo_gui_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
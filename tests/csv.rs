@@ -0,0 +1,201 @@
+mod common;
+
+use chrono::Local;
+use todo_gui::csv_io::{CsvTable, DuplicateAction, ExportScope, Field, ImportWizard};
+use todo_gui::recurrence::Recurrence;
+use todo_gui::todo::{Filter, Message, Priority, TodoDocument, TodoId, TodoState};
+use common::add_todo;
+
+/// Opens the CSV panel and the import wizard on `data`.
+fn start_import(state: &mut TodoState, data: &str) {
+    if state.csv_panel().is_none() {
+        state.update(Message::ToggleCsvPanel);
+    }
+    state.start_import(CsvTable::parse(data));
+}
+
+#[test]
+fn exported_todos_import_unchanged() {
+    let mut state = TodoState::default();
+    let milk = add_todo(&mut state, "Milk");
+    let report = add_todo(&mut state, "Write report");
+    state.update(Message::EditingTodo(milk, String::from("Milk #shopping")));
+    state.update(Message::FinishEditing);
+    state.update(Message::PriorityChanged(milk, Priority::High));
+    state.update(Message::DescriptionChanged(milk, String::from("Oat, \"barista\" edition\nTwo cartons")));
+    state.update(Message::SubtaskInputChanged(milk, String::from("Check fridge")));
+    state.update(Message::AddSubtask(milk));
+    state.update(Message::RecurrenceChanged(milk, Some(Recurrence::weekdays())));
+    state.update(Message::ToggleTodo(report));
+
+    let (all, count) = state.export_csv(ExportScope::All).unwrap();
+    assert_eq!(count, 2);
+    assert!(all.starts_with("id,title,description,completed,created_at"));
+    state.update(Message::FilterChanged(Filter::Active));
+    assert_eq!(state.export_csv(ExportScope::Filtered).unwrap().1, 1);
+
+    let wizard = ImportWizard::new(CsvTable::parse(&all).unwrap());
+    assert!(!wizard.mapping.contains(&Field::Ignore));
+    let preview = wizard.preview(&TodoDocument::default(), Local::now());
+    assert_eq!(preview.len(), 2);
+    for row in preview {
+        let imported = row.todo.unwrap();
+        let original = &state.document().todos[&imported.id];
+        assert_eq!(imported.title, original.title);
+        assert_eq!(imported.description, original.description);
+        assert_eq!(imported.completed, original.completed);
+        assert_eq!(imported.created_at, original.created_at);
+        assert_eq!(imported.completed_at, original.completed_at);
+        assert_eq!(imported.due, original.due);
+        assert_eq!(imported.priority, original.priority);
+        assert_eq!(imported.tags, original.tags);
+        assert_eq!(imported.subtasks, original.subtasks);
+        assert_eq!(imported.recurrence, original.recurrence);
+        assert_eq!(row.list.as_deref(), Some("Inbox"));
+    }
+}
+
+#[test]
+fn exported_cells_are_not_run_as_formulas_or_split() {
+    let mut state = TodoState::default();
+    let sum = add_todo(&mut state, "=SUM(A1:A9)");
+    state.update(Message::DescriptionChanged(sum, String::from("-1 for \"this\"")));
+    state.update(Message::SubtaskInputChanged(sum, String::from("Ask Bob; then Alice")));
+    state.update(Message::AddSubtask(sum));
+    state.update(Message::SubtaskInputChanged(sum, String::from("C:\\temp\\")));
+    state.update(Message::AddSubtask(sum));
+    add_todo(&mut state, "'quoted'");
+    add_todo(&mut state, "@home: call");
+
+    let (data, _) = state.export_csv(ExportScope::All).unwrap();
+    let table = CsvTable::parse(&data).unwrap();
+    for row in &table.rows {
+        for cell in row {
+            assert!(!cell.starts_with(['=', '+', '-', '@']), "{} would be a formula", cell);
+        }
+    }
+
+    let preview = ImportWizard::new(table).preview(&TodoDocument::default(), Local::now());
+    assert_eq!(preview.len(), 3);
+    for row in preview {
+        let imported = row.todo.unwrap();
+        let original = &state.document().todos[&imported.id];
+        assert_eq!(imported.title, original.title);
+        assert_eq!(imported.description, original.description);
+        assert_eq!(imported.subtasks, original.subtasks);
+    }
+}
+
+#[test]
+fn only_english_words_are_read_as_completion() {
+    let mut state = TodoState::default();
+    start_import(&mut state, "title,completed\nPay rent,yes\nFile taxes,ja\n");
+    let preview = state.import_preview();
+    assert!(preview[0].todo.as_ref().unwrap().completed);
+    assert!(preview[1].todo.as_ref().unwrap_err().contains("ja"));
+}
+
+#[test]
+fn columns_are_mapped_by_header_and_by_choice() {
+    let mut state = TodoState::default();
+    start_import(
+        &mut state,
+        "\u{feff}Task;Deadline;Done;Project;Notes\n\
+         Pay rent;01.11.2026;no;Home;Transfer\n\
+         ;02.11.2026;no;Home;\n\
+         File taxes;someday;no;;\n",
+    );
+    let wizard = state.csv_panel().unwrap().import.clone().unwrap();
+    assert_eq!(
+        wizard.mapping,
+        vec![Field::Title, Field::Due, Field::Completed, Field::List, Field::Description]
+    );
+
+    // A field is mapped to one column at a time
+    state.update(Message::ImportFieldChanged(4, Field::Title));
+    let wizard = state.csv_panel().unwrap().import.clone().unwrap();
+    assert_eq!(wizard.mapping[0], Field::Ignore);
+    state.update(Message::ImportFieldChanged(0, Field::Title));
+    state.update(Message::ImportFieldChanged(4, Field::Description));
+
+    let preview = state.import_preview();
+    assert_eq!(preview[0].line, 2);
+    let rent = preview[0].todo.as_ref().unwrap();
+    assert_eq!(rent.due.unwrap().format("%Y-%m-%d %H:%M").to_string(), "2026-11-01 23:59");
+    assert!(preview[1].todo.is_err());
+    assert!(preview[2].todo.as_ref().unwrap_err().contains("someday"));
+
+    state.update(Message::ConfirmImport);
+    assert!(state.csv_panel().unwrap().import.is_none());
+    let home = state.lists().find(|list| list.name == "Home").unwrap().id;
+    let rent = state.document().todos.values().find(|todo| todo.title == "Pay rent").unwrap();
+    assert_eq!(rent.list_id, home);
+    assert_eq!(rent.description, "Transfer");
+    assert_eq!(state.document().todos.len(), 1);
+
+    state.update(Message::Undo);
+    assert!(state.document().todos.is_empty());
+}
+
+#[test]
+fn duplicates_are_skipped_updated_or_kept() {
+    let mut state = TodoState::default();
+    let milk = add_todo(&mut state, "Milk");
+    let data = format!(
+        "id,title,priority\n{},Oat milk,urgent\n,milk ,low\n,Bread,\n,Bread,high\n",
+        milk
+    );
+
+    start_import(&mut state, &data);
+    let preview = state.import_preview();
+    let duplicates: Vec<Option<TodoId>> = preview.iter().map(|row| row.duplicate).collect();
+    assert_eq!(duplicates[..3], [Some(milk), Some(milk), None]);
+    assert!(duplicates[3].is_some());
+    state.update(Message::ConfirmImport);
+    assert_eq!(state.document().todos.len(), 2);
+    assert_eq!(state.document().todos[&milk].title, "Milk");
+
+    start_import(&mut state, &data);
+    state.update(Message::ImportDuplicatesChanged(DuplicateAction::Update));
+    state.update(Message::ConfirmImport);
+    assert_eq!(state.document().todos.len(), 2);
+    // Rows are applied in order, the last one wins
+    assert_eq!(state.document().todos[&milk].title, "milk");
+    assert_eq!(state.document().todos[&milk].priority, Priority::Low);
+    let bread = state.document().todos.values().find(|todo| todo.title == "Bread").unwrap();
+    assert_eq!(bread.priority, Priority::High);
+
+    start_import(&mut state, &data);
+    state.update(Message::ImportDuplicatesChanged(DuplicateAction::KeepBoth));
+    state.update(Message::ConfirmImport);
+    assert_eq!(state.document().todos.len(), 6);
+    assert_eq!(state.document().todos[&milk].title, "milk");
+}
+
+#[test]
+fn imported_todos_keep_their_timestamps() {
+    let mut state = TodoState::default();
+    let milk = add_todo(&mut state, "Milk");
+    start_import(
+        &mut state,
+        &format!(
+            "id,title,created_at,updated_at\n\
+             ,Pay rent,2026-01-02 10:00,2026-02-03 11:30\n\
+             {},Oat milk,2026-01-02 10:00,2026-02-03 11:30\n",
+            milk
+        ),
+    );
+    state.update(Message::ImportDuplicatesChanged(DuplicateAction::Update));
+    state.update(Message::ConfirmImport);
+
+    let rent = state.document().todos.values().find(|todo| todo.title == "Pay rent").unwrap();
+    assert_eq!(rent.created_at.format("%Y-%m-%d %H:%M").to_string(), "2026-01-02 10:00");
+    assert_eq!(rent.updated_at.unwrap().format("%Y-%m-%d %H:%M").to_string(), "2026-02-03 11:30");
+    assert!(rent.activity.is_empty());
+
+    // Updating a todo already here is a change made now
+    let milk = &state.document().todos[&milk];
+    assert_eq!(milk.title, "Oat milk");
+    assert!(milk.updated_at.unwrap() > rent.updated_at.unwrap());
+    assert!(!milk.activity.is_empty());
+}
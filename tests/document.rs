@@ -0,0 +1,32 @@
+mod common;
+
+use todo_gui::todo::{Filter, Message, TodoDocument, TodoState};
+use common::add_todo;
+
+#[test]
+fn ui_state_stays_out_of_the_document() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Milk");
+    state.take_changes();
+    let document = state.document().clone();
+
+    state.update(Message::FilterChanged(Filter::Completed));
+    state.update(Message::SearchQueryChanged(String::from("mil")));
+    state.update(Message::ToggleExpanded(id));
+    state.update(Message::EditingTodo(id, String::from("Half typed")));
+    state.update(Message::InputChanged(String::from("Bread")));
+    assert_eq!(state.document(), &document);
+    assert!(!state.has_changes());
+
+    let value = serde_json::to_value(state.document()).unwrap();
+    for key in ["filter", "search_query", "editing", "input_value", "expanded"] {
+        assert!(value.get(key).is_none(), "{} was saved", key);
+    }
+
+    // A new state starts from the document with fresh UI state
+    let reopened = TodoState::new(TodoDocument::from_value(value).unwrap());
+    assert_eq!(reopened.document(), &document);
+    assert_eq!(reopened.filter(), &Filter::All);
+    assert!(reopened.search_query().is_empty());
+    assert!(reopened.editing().is_none());
+}
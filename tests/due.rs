@@ -0,0 +1,71 @@
+mod common;
+
+use chrono::{Duration, Local, NaiveDate, Timelike};
+use todo_gui::todo::{end_of_day, parse_due, DueStatus, Message, SortOrder, TodoState};
+use common::add_todo;
+
+#[test]
+fn due_status_follows_the_clock() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Pay rent");
+    let now = Local::now();
+    assert_eq!(state.document().todos[&id].due_status(now), None);
+
+    state.update(Message::SetDueDate(id, now - Duration::hours(1)));
+    assert_eq!(state.document().todos[&id].due_status(now), Some(DueStatus::Overdue));
+    state.update(Message::SetDueDate(id, end_of_day(now.date_naive()).unwrap()));
+    let status = state.document().todos[&id].due_status(now);
+    assert!(matches!(status, Some(DueStatus::DueToday | DueStatus::Overdue)));
+    state.update(Message::ShiftDueDate(id, Duration::days(2)));
+    assert_eq!(state.document().todos[&id].due_status(now), Some(DueStatus::Upcoming));
+
+    // Completed todos are never overdue
+    state.update(Message::ShiftDueDate(id, Duration::days(-7)));
+    state.update(Message::ToggleTodo(id));
+    assert_eq!(state.document().todos[&id].due_status(now), None);
+
+    state.update(Message::ClearDueDate(id));
+    assert!(state.document().todos[&id].due.is_none());
+}
+
+#[test]
+fn sorts_by_due_date_with_undated_todos_last() {
+    let mut state = TodoState::default();
+    let now = Local::now();
+    let later = add_todo(&mut state, "Later");
+    add_todo(&mut state, "Undated");
+    let soon = add_todo(&mut state, "Soon");
+    add_todo(&mut state, "Newest undated");
+    state.update(Message::SetDueDate(later, now + Duration::days(3)));
+    state.update(Message::SetDueDate(soon, now - Duration::days(1)));
+
+    state.update(Message::SortChanged(SortOrder::DueDate));
+    let titles: Vec<&str> = state.filtered_todos().iter().map(|todo| todo.title.as_str()).collect();
+    assert_eq!(titles, ["Soon", "Later", "Newest undated", "Undated"]);
+}
+
+#[test]
+fn due_dates_are_typed_in() {
+    let christmas = NaiveDate::from_ymd_opt(2026, 12, 24).unwrap();
+    assert_eq!(parse_due("2026-12-24"), end_of_day(christmas));
+    assert_eq!(parse_due(" 24.12.2026 "), end_of_day(christmas));
+    let evening = parse_due("2026-12-24  18:30").unwrap();
+    assert_eq!((evening.date_naive(), evening.hour(), evening.minute()), (christmas, 18, 30));
+    assert!(parse_due("24/12/2026").is_none());
+    assert!(parse_due("2026-12-24 late").is_none());
+
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Send cards");
+    state.update(Message::DueInputChanged(id, String::from("next week")));
+    state.update(Message::SubmitDueDate(id));
+    assert!(state.document().todos[&id].due.is_none());
+    assert_eq!(state.due_input(id), "next week");
+
+    state.update(Message::DueInputChanged(id, String::from("2026-12-20")));
+    state.update(Message::SubmitDueDate(id));
+    assert_eq!(state.document().todos[&id].due, end_of_day(NaiveDate::from_ymd_opt(2026, 12, 20).unwrap()));
+    assert_eq!(state.due_input(id), "");
+
+    state.update(Message::Undo);
+    assert!(state.document().todos[&id].due.is_none());
+}
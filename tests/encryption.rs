@@ -0,0 +1,89 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use todo_gui::error::TodoError;
+use todo_gui::storage::encrypted::is_encrypted;
+use todo_gui::storage::{EncryptedStorage, JsonFileStorage, Storage};
+use todo_gui::todo::{Message, TodoState};
+use common::temp_dir;
+
+/// Saves a plain workspace with one open and one archived todo.
+fn plain_workspace(path: &Path) -> TodoState {
+    let mut state = TodoState::default();
+    for title in ["Call ACME about the contract", "Rotate the VPN secret"] {
+        state.update(Message::AddTodo);
+        state.update(Message::InputChanged(title.to_string()));
+        state.update(Message::AddTodo);
+    }
+    let done = state.document().todos.values().find(|todo| todo.title.starts_with("Rotate")).unwrap().id;
+    state.update(Message::ToggleTodo(done));
+    state.update(Message::ArchiveCompleted);
+
+    let storage = JsonFileStorage::new(path.to_path_buf());
+    let changes = state.take_changes();
+    storage.save_changes(state.document(), &changes).unwrap();
+    state
+}
+
+#[test]
+fn encrypting_hides_the_todos_and_archive() {
+    let dir = temp_dir("encrypt");
+    let path = dir.join("todos.json");
+    let state = plain_workspace(&path);
+
+    let storage = EncryptedStorage::encrypt(path.clone(), "correct horse").unwrap();
+    assert!(is_encrypted(&path));
+    for file in ["todos.json", "todos-archive.json"] {
+        let data = fs::read_to_string(dir.join(file)).unwrap();
+        assert!(!data.contains("ACME") && !data.contains("VPN"), "{} is readable", file);
+    }
+    assert!(storage.backups().is_empty());
+    // not even for a moment, a plain copy could be left behind by a crash
+    assert!(!dir.join("backups").exists());
+    assert_eq!(storage.load().unwrap().unwrap().todos, state.document().todos);
+    assert_eq!(storage.load_archive().unwrap().len(), 1);
+
+    // A plain reader fails instead of showing an empty list
+    assert!(JsonFileStorage::new(path).load().is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unlocking_needs_the_right_passphrase() {
+    let dir = temp_dir("unlock");
+    let path = dir.join("todos.json");
+    let state = plain_workspace(&path);
+    EncryptedStorage::encrypt(path.clone(), "correct horse").unwrap();
+
+    assert_eq!(
+        EncryptedStorage::unlock(path.clone(), "wrong horse").unwrap_err(),
+        TodoError::WrongPassphrase
+    );
+    let storage = EncryptedStorage::unlock(path.clone(), "correct horse").unwrap();
+    assert_eq!(storage.load().unwrap().unwrap().todos, state.document().todos);
+
+    let changed = storage.change_passphrase("battery staple").unwrap();
+    assert!(EncryptedStorage::unlock(path.clone(), "correct horse").is_err());
+    assert!(EncryptedStorage::unlock(path.clone(), "battery staple").is_ok());
+    assert_eq!(changed.load_archive().unwrap().len(), 1);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn decrypting_restores_plain_json() {
+    let dir = temp_dir("decrypt");
+    let path = dir.join("todos.json");
+    let state = plain_workspace(&path);
+    let storage = EncryptedStorage::encrypt(path.clone(), "correct horse").unwrap();
+
+    let plain = storage.decrypt().unwrap();
+    assert!(!is_encrypted(&path));
+    assert!(fs::read_to_string(&path).unwrap().contains("ACME"));
+    assert_eq!(plain.load().unwrap().unwrap().todos, state.document().todos);
+    assert_eq!(plain.load_archive().unwrap()[0].title, "Rotate the VPN secret");
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use todo_gui::todo::{Message, TodoState};
+use common::add_todo;
+
+#[test]
+fn undo_and_redo_a_deletion() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Buy milk");
+    state.take_changes();
+
+    state.update(Message::DeleteTodo(id));
+    assert!(state.notice().is_some());
+
+    state.update(Message::Undo);
+    assert!(!state.document().todos[&id].is_trashed());
+    assert!(state.notice().is_none());
+    assert!(state.take_changes().full);
+
+    state.update(Message::Redo);
+    assert!(state.document().todos[&id].is_trashed());
+    assert!(!state.can_redo());
+}
+
+#[test]
+fn typing_a_description_is_undone_at_once() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Write report");
+
+    for description in ["D", "Dr", "Draft"] {
+        state.update(Message::DescriptionChanged(id, description.to_string()));
+    }
+    state.update(Message::Undo);
+    assert_eq!(state.document().todos[&id].description, "");
+
+    state.update(Message::Undo);
+    assert!(state.document().todos.is_empty());
+    assert!(!state.can_undo());
+}
+
+#[test]
+fn a_pause_in_typing_starts_a_new_undo_step() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Write report");
+    state.update(Message::DescriptionChanged(id, String::from("Draft")));
+    // Saving takes the changes once typing paused
+    state.take_changes();
+    state.update(Message::DescriptionChanged(id, String::from("Draft, then")));
+    state.update(Message::DescriptionChanged(id, String::from("Draft, then review")));
+
+    state.update(Message::Undo);
+    assert_eq!(state.document().todos[&id].description, "Draft");
+    state.update(Message::Redo);
+    assert_eq!(state.document().todos[&id].description, "Draft, then review");
+}
+
+#[test]
+fn new_change_clears_redo_and_ui_messages_are_not_recorded() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Call Anna");
+    state.update(Message::ToggleTodo(id));
+    state.update(Message::Undo);
+    assert!(state.can_redo());
+
+    state.update(Message::SearchQueryChanged(String::from("anna")));
+    assert!(state.can_redo());
+
+    state.update(Message::ToggleTodo(id));
+    assert!(!state.can_redo());
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use todo_gui::todo::{ListSelection, Message, TodoState, INBOX_LIST_ID};
+use common::add_todo;
+
+fn shown(state: &TodoState) -> Vec<&str> {
+    let mut titles: Vec<&str> = state.filtered_todos().iter().map(|todo| todo.title.as_str()).collect();
+    titles.sort();
+    titles
+}
+
+#[test]
+fn todos_are_scoped_to_the_selected_list() {
+    let mut state = TodoState::default();
+    let milk = add_todo(&mut state, "Milk");
+    state.update(Message::NewListNameChanged(String::from("Work")));
+    state.update(Message::CreateList);
+    let work = state.lists().find(|list| list.name == "Work").unwrap().id;
+    assert_eq!(state.selected_list(), ListSelection::List(work));
+
+    // New todos go into the selected list
+    let report = add_todo(&mut state, "Report");
+    assert_eq!(state.document().todos[&report].list_id, work);
+    assert_eq!(shown(&state), ["Report"]);
+    state.update(Message::SelectList(ListSelection::All));
+    assert_eq!(shown(&state), ["Milk", "Report"]);
+
+    state.update(Message::MoveTodo(milk, work));
+    state.update(Message::ToggleTodo(milk));
+    assert_eq!(state.list_counts(work), (1, 2));
+    assert_eq!(state.list_counts(INBOX_LIST_ID), (0, 0));
+
+    // Archived lists are left out of all lists, the inbox cannot be archived
+    state.update(Message::SetListArchived(work, true));
+    state.update(Message::SetListArchived(INBOX_LIST_ID, true));
+    assert!(shown(&state).is_empty());
+    assert!(!state.document().lists[&INBOX_LIST_ID].archived);
+    state.update(Message::SelectList(ListSelection::List(work)));
+    assert_eq!(shown(&state), ["Milk", "Report"]);
+
+    // Deleting a list moves its todos to the trash
+    state.update(Message::DeleteList(work));
+    assert_eq!(state.selected_list(), ListSelection::All);
+    assert!(state.document().todos[&report].is_trashed());
+    assert_eq!(state.trash_count(), 2);
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use todo_gui::lock::WorkspaceLock;
+use todo_gui::todo::{Filter, Message, TodoState};
+
+#[test]
+fn a_second_instance_does_not_get_the_lock() {
+    let dir = std::env::temp_dir().join(format!("todo_gui_lock_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("todos.json");
+
+    let first = WorkspaceLock::acquire(&path).unwrap();
+    assert!(first.is_some());
+    assert!(dir.join("todos.json.lock").exists());
+    assert!(WorkspaceLock::acquire(&path).unwrap().is_none());
+
+    // Other files are locked independently
+    assert!(WorkspaceLock::acquire(&dir.join("team.json")).unwrap().is_some());
+
+    drop(first);
+    assert!(WorkspaceLock::acquire(&path).unwrap().is_some());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn read_only_state_ignores_edits() {
+    let mut state = TodoState::default();
+    state.update(Message::AddTodo);
+    state.update(Message::InputChanged(String::from("Buy milk")));
+    state.update(Message::AddTodo);
+    let id = *state.document().todos.keys().next().unwrap();
+    state.take_changes();
+
+    state.set_read_only(true);
+    state.update(Message::ToggleTodo(id));
+    state.update(Message::DeleteTodo(id));
+    state.update(Message::AddTodo);
+    assert!(!state.document().todos[&id].completed);
+    assert!(!state.document().todos[&id].is_trashed());
+    assert!(!state.has_changes());
+
+    // Looking around still works
+    state.update(Message::FilterChanged(Filter::Completed));
+    assert!(state.filtered_todos().is_empty());
+
+    state.set_read_only(false);
+    state.update(Message::ToggleTodo(id));
+    assert!(state.document().todos[&id].completed);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs;
+use todo_gui::merge::Resolution;
+use todo_gui::storage::{ChangeSet, FileStamp, JsonFileStorage, Storage};
+use todo_gui::todo::{Message, TodoDocument, TodoId, TodoState};
+use common::{add_todo, temp_dir};
+
+/// A state holding "Milk" and "Bread", with `base` being its document.
+fn synced_state() -> (TodoState, TodoDocument, TodoId, TodoId) {
+    let mut state = TodoState::default();
+    let milk = add_todo(&mut state, "Milk");
+    let bread = add_todo(&mut state, "Bread");
+    state.take_changes();
+    let base = state.document().clone();
+    (state, base, milk, bread)
+}
+
+#[test]
+fn changes_to_different_todos_are_combined() {
+    let (mut state, base, milk, bread) = synced_state();
+    state.update(Message::ToggleTodo(milk));
+
+    let mut external = TodoState::new(base.clone());
+    external.update(Message::DeletePermanently(bread));
+    let eggs = add_todo(&mut external, "Eggs");
+    let theirs = external.document().clone();
+
+    state.merge_external(&base, &theirs, ChangeSet::default());
+    assert!(state.conflicts().is_empty());
+    assert!(state.document().todos[&milk].completed);
+    assert!(!state.document().todos.contains_key(&bread));
+    assert!(state.document().todos.contains_key(&eggs));
+    assert!(!state.can_undo());
+
+    // Only the local change differs from the file
+    let changes = state.take_changes();
+    assert_eq!(changes.todos.into_iter().collect::<Vec<_>>(), vec![milk]);
+}
+
+#[test]
+fn conflicting_changes_keep_mine_until_resolved() {
+    let (mut state, base, milk, bread) = synced_state();
+    state.update(Message::ToggleTodo(milk));
+    state.update(Message::ToggleTodo(bread));
+
+    let mut external = TodoState::new(base.clone());
+    external.update(Message::DescriptionChanged(milk, String::from("Oat milk")));
+    external.update(Message::DeletePermanently(bread));
+    let theirs = external.document().clone();
+
+    state.merge_external(&base, &theirs, ChangeSet::default());
+    assert_eq!(state.conflicts().len(), 2);
+    assert!(state.document().todos[&milk].completed);
+    assert!(state.document().todos[&bread].completed);
+    state.take_changes();
+
+    state.update(Message::ResolveConflict(milk, Resolution::UseTheirs));
+    assert_eq!(state.document().todos[&milk], theirs.todos[&milk]);
+    state.update(Message::ResolveAllConflicts(Resolution::KeepMine));
+    assert!(state.document().todos[&bread].completed);
+    assert!(state.conflicts().is_empty());
+
+    let changes = state.take_changes();
+    assert!(changes.todos.contains(&milk));
+    assert!(!changes.todos.contains(&bread));
+}
+
+#[test]
+fn json_storage_exposes_the_file_to_watch() {
+    let dir = temp_dir("merge");
+    let path = dir.join("todos.json");
+    let storage = JsonFileStorage::new(path.clone());
+    assert_eq!(storage.data_path(), Some(path.as_path()));
+    assert_eq!(FileStamp::of(&path), None);
+
+    let (state, ..) = synced_state();
+    storage.save(state.document()).unwrap();
+    let saved = FileStamp::of(&path);
+    assert!(saved.is_some());
+
+    fs::write(&path, fs::read_to_string(&path).unwrap().replace("Milk", "Whole milk")).unwrap();
+    assert_ne!(FileStamp::of(&path), saved);
+
+    fs::remove_dir_all(dir).unwrap();
+}
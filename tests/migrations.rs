@@ -0,0 +1,256 @@
+mod common;
+
+use serde_json::json;
+use todo_gui::error::TodoError;
+use todo_gui::migrations::{detect_version, migrate, migrate_archived_todos, CURRENT_VERSION};
+use todo_gui::storage::{JsonFileStorage, Storage};
+use todo_gui::todo::{Priority, SortOrder, Todo, TodoDocument, INBOX_LIST_ID};
+use common::temp_dir;
+
+/// `todos.json` as written by the first release, a full `TodoState` snapshot.
+const V0_INITIAL: &str = r#"{
+  "todos": {
+    "1": {
+      "id": 1,
+      "title": "Buy milk",
+      "description": "2 liters",
+      "completed": true,
+      "created_at": "2024-05-01T10:00:00+02:00"
+    }
+  },
+  "next_id": 2,
+  "filter": "Active",
+  "search_query": "mil",
+  "editing": [0, "half typed"]
+}"#;
+
+/// A `TodoState` snapshot carrying the fields added before the UI state was
+/// split off (due dates, priorities, tags, lists, sort order).
+const V0_WITH_LISTS: &str = r#"{
+  "todos": {
+    "3": {
+      "id": 3,
+      "title": "Release notes",
+      "description": "",
+      "completed": false,
+      "created_at": "2024-06-01T09:00:00+02:00",
+      "due": "2024-06-07T17:00:00+02:00",
+      "priority": "High",
+      "tags": ["release"],
+      "list_id": 2
+    }
+  },
+  "next_id": 4,
+  "lists": {
+    "1": { "id": 1, "name": "Inbox", "archived": false },
+    "2": { "id": 2, "name": "Work", "archived": false }
+  },
+  "next_list_id": 3,
+  "filter": "All",
+  "search_query": "",
+  "editing": null,
+  "sort_order": "Priority",
+  "selected_tags": ["release"],
+  "tag_match": "All",
+  "selected_list": { "List": 2 }
+}"#;
+
+/// The bare `TodoDocument`, before the `version` field existed.
+const V1: &str = r#"{
+  "todos": {
+    "1": {
+      "id": 1,
+      "title": "Standup",
+      "description": "",
+      "completed": false,
+      "created_at": "2024-07-01T09:00:00+02:00",
+      "recurrence": "Daily"
+    }
+  },
+  "next_id": 2,
+  "lists": {
+    "1": { "id": 1, "name": "Inbox", "archived": false }
+  },
+  "next_list_id": 2,
+  "settings": { "sort_order": "DueDate" }
+}"#;
+
+/// A versioned document with numeric ids and two instances of a recurring todo.
+const V2: &str = r#"{
+  "version": 2,
+  "todos": {
+    "4": {
+      "id": 4,
+      "title": "Water plants",
+      "description": "",
+      "completed": true,
+      "created_at": "2024-08-01T09:00:00+02:00",
+      "series": 4
+    },
+    "7": {
+      "id": 7,
+      "title": "Water plants",
+      "description": "",
+      "completed": false,
+      "created_at": "2024-08-08T09:00:00+02:00",
+      "recurrence": { "AfterCompletion": 7 },
+      "series": 4
+    }
+  },
+  "next_id": 8
+}"#;
+
+fn find<'a>(document: &'a TodoDocument, title: &str) -> &'a Todo {
+    document.todos.values().find(|todo| todo.title == title).unwrap()
+}
+
+#[test]
+fn detects_historical_versions() {
+    let parse = |data: &str| serde_json::from_str::<serde_json::Value>(data).unwrap();
+
+    assert_eq!(detect_version(&parse(V0_INITIAL)), Ok(0));
+    assert_eq!(detect_version(&parse(V0_WITH_LISTS)), Ok(0));
+    assert_eq!(detect_version(&parse(V1)), Ok(1));
+    assert_eq!(detect_version(&parse(V2)), Ok(2));
+    assert_eq!(detect_version(&json!({ "version": 2, "todos": {} })), Ok(2));
+}
+
+#[test]
+fn migrates_initial_v0_file() {
+    let document = TodoDocument::from_json(V0_INITIAL).unwrap();
+
+    assert_eq!(document.version, CURRENT_VERSION);
+    assert_eq!(document.migrated_from, Some(0));
+    assert_eq!(document.todos.len(), 1);
+    let todo = find(&document, "Buy milk");
+    assert_eq!(todo.title, "Buy milk");
+    assert_eq!(todo.description, "2 liters");
+    assert!(todo.completed);
+    assert_eq!(todo.list_id, INBOX_LIST_ID);
+    assert!(document.lists.contains_key(&INBOX_LIST_ID));
+    assert_eq!(document.settings.sort_order, SortOrder::Newest);
+}
+
+#[test]
+fn migrates_v0_file_with_lists_and_keeps_sort_order() {
+    let document = TodoDocument::from_json(V0_WITH_LISTS).unwrap();
+
+    assert_eq!(document.settings.sort_order, SortOrder::Priority);
+    assert_eq!(document.lists[&2].name, "Work");
+    assert_eq!(document.next_list_id, 3);
+    let todo = find(&document, "Release notes");
+    assert_eq!(todo.priority, Priority::High);
+    assert_eq!(todo.list_id, 2);
+    assert!(todo.tags.contains("release"));
+    assert!(todo.due.is_some());
+}
+
+#[test]
+fn v0_ui_state_is_dropped() {
+    let value = migrate(serde_json::from_str(V0_WITH_LISTS).unwrap()).unwrap();
+
+    for key in ["filter", "search_query", "editing", "selected_tags", "tag_match", "sort_order"] {
+        assert!(value.get(key).is_none(), "{} was not removed", key);
+    }
+    assert_eq!(value["version"], json!(CURRENT_VERSION));
+}
+
+#[test]
+fn migrates_v1_file() {
+    let document = TodoDocument::from_json(V1).unwrap();
+
+    assert_eq!(document.version, CURRENT_VERSION);
+    assert_eq!(document.settings.sort_order, SortOrder::DueDate);
+    assert!(find(&document, "Standup").recurrence.is_some());
+}
+
+#[test]
+fn current_version_round_trips() {
+    let document = TodoDocument::from_json(V1).unwrap();
+    let json = serde_json::to_string(&document).unwrap();
+
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["version"], json!(CURRENT_VERSION));
+
+    let reloaded = TodoDocument::from_json(&json).unwrap();
+    assert_eq!(reloaded.todos, document.todos);
+    assert_eq!(reloaded.migrated_from, None);
+}
+
+#[test]
+fn migrates_numeric_ids_to_uuids_keeping_series() {
+    let value = migrate(serde_json::from_str(V2).unwrap()).unwrap();
+    assert!(value.get("next_id").is_none());
+
+    let document = TodoDocument::from_value(value).unwrap();
+    assert_eq!(document.migrated_from, None);
+    let instances: Vec<&Todo> = document.todos.values().collect();
+    assert_eq!(instances.len(), 2);
+    assert_ne!(instances[0].id, instances[1].id);
+    assert_eq!(instances[0].series, instances[1].series);
+
+    let first = document.todos.values().find(|todo| todo.completed).unwrap();
+    assert_eq!(first.series, Some(first.id));
+    for (key, todo) in &document.todos {
+        assert_eq!(*key, todo.id);
+    }
+}
+
+#[test]
+fn migrating_a_file_twice_gives_the_same_ids() {
+    let ids = || {
+        let document = TodoDocument::from_json(V2).unwrap();
+        let mut ids: Vec<_> = document.todos.values().map(|todo| (todo.id, todo.series)).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(ids(), ids());
+
+    // and the archive derives them the same way
+    let mut archived: Vec<serde_json::Value> = serde_json::from_str::<serde_json::Value>(V2).unwrap()["todos"]
+        .as_object()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect();
+    assert!(migrate_archived_todos(&mut archived));
+    let mut archived: Vec<_> = archived
+        .into_iter()
+        .map(|todo| serde_json::from_value::<Todo>(todo).unwrap())
+        .map(|todo| (todo.id, todo.series))
+        .collect();
+    archived.sort();
+    assert_eq!(archived, ids());
+}
+
+#[test]
+fn migrated_archive_keeps_its_new_ids() {
+    let dir = temp_dir("archive_v2");
+    std::fs::write(dir.join("todos-archive.json"), V2).unwrap();
+    let storage = JsonFileStorage::new(dir.join("todos.json"));
+
+    let archive = storage.load_archive().unwrap();
+    assert_eq!(archive.len(), 2);
+    let mut ids: Vec<_> = archive.iter().map(|todo| todo.id).collect();
+    let mut reloaded: Vec<_> = storage.load_archive().unwrap().iter().map(|todo| todo.id).collect();
+    ids.sort();
+    reloaded.sort();
+    assert_eq!(reloaded, ids);
+
+    // So restoring a todo takes it out of the archive
+    storage.write_archive(&[], &ids[..1].iter().copied().collect()).unwrap();
+    let left: Vec<_> = storage.load_archive().unwrap().iter().map(|todo| todo.id).collect();
+    assert_eq!(left, ids[1..]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn rejects_newer_versions() {
+    let data = json!({ "version": CURRENT_VERSION + 1, "todos": {}, "next_id": 1 }).to_string();
+
+    assert!(matches!(
+        TodoDocument::from_json(&data),
+        Err(TodoError::UnsupportedVersion { .. })
+    ));
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use chrono::{Duration, Local};
+use todo_gui::todo::{Message, Priority, SortOrder, TodoState};
+use common::add_todo;
+
+#[test]
+fn sorts_by_priority_then_due_date() {
+    let mut state = TodoState::default();
+    let now = Local::now();
+    let low = add_todo(&mut state, "Low");
+    let urgent = add_todo(&mut state, "Urgent");
+    let high_later = add_todo(&mut state, "High later");
+    let high_soon = add_todo(&mut state, "High soon");
+    add_todo(&mut state, "None");
+    state.update(Message::PriorityChanged(low, Priority::Low));
+    state.update(Message::PriorityChanged(urgent, Priority::Urgent));
+    state.update(Message::PriorityChanged(high_later, Priority::High));
+    state.update(Message::PriorityChanged(high_soon, Priority::High));
+    state.update(Message::SetDueDate(high_later, now + Duration::days(2)));
+    state.update(Message::SetDueDate(high_soon, now + Duration::days(1)));
+    assert_eq!(state.document().todos[&low].priority, Priority::Low);
+
+    state.update(Message::SortChanged(SortOrder::Priority));
+    let titles: Vec<&str> = state.filtered_todos().iter().map(|todo| todo.title.as_str()).collect();
+    assert_eq!(titles, ["Urgent", "High soon", "High later", "Low", "None"]);
+    assert!(Priority::Urgent > Priority::High && Priority::Low > Priority::None);
+}
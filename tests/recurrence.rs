@@ -0,0 +1,99 @@
+mod common;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Weekday};
+use todo_gui::recurrence::Recurrence;
+use todo_gui::todo::{Message, TodoState};
+use common::add_todo;
+
+fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Local> {
+    Local.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+}
+
+fn next_date(recurrence: Recurrence, due: DateTime<Local>) -> NaiveDate {
+    recurrence.next_due(due, due).date_naive()
+}
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn monthly_is_clamped_to_short_months() {
+    assert_eq!(next_date(Recurrence::MonthlyOnDay(31), at(2026, 1, 31, 9)), date(2026, 2, 28));
+    assert_eq!(next_date(Recurrence::MonthlyOnDay(31), at(2028, 1, 31, 9)), date(2028, 2, 29));
+    assert_eq!(next_date(Recurrence::MonthlyOnDay(31), at(2026, 3, 31, 9)), date(2026, 4, 30));
+    // The day is kept even after a short month clamped it
+    assert_eq!(next_date(Recurrence::MonthlyOnDay(31), at(2026, 2, 28, 9)), date(2026, 3, 31));
+    assert_eq!(next_date(Recurrence::MonthlyOnDay(15), at(2026, 12, 15, 9)), date(2027, 1, 15));
+    assert_eq!(next_date(Recurrence::Daily, at(2026, 12, 31, 9)), date(2027, 1, 1));
+}
+
+#[test]
+fn weekly_moves_to_the_next_matching_weekday() {
+    // 2026-10-16 is a Friday
+    assert_eq!(next_date(Recurrence::weekdays(), at(2026, 10, 16, 9)), date(2026, 10, 19));
+    assert_eq!(next_date(Recurrence::weekdays(), at(2026, 10, 19, 9)), date(2026, 10, 20));
+    let weekly = Recurrence::Weekly(vec![Weekday::Fri]);
+    assert_eq!(next_date(weekly, at(2026, 10, 16, 9)), date(2026, 10, 23));
+    assert_eq!(next_date(Recurrence::Weekly(Vec::new()), at(2026, 10, 16, 9)), date(2026, 10, 23));
+}
+
+#[test]
+fn after_completion_counts_from_the_completion_and_keeps_the_time() {
+    let due = at(2026, 10, 1, 9);
+    let next = Recurrence::AfterCompletion(3).next_due(due, at(2026, 10, 10, 18));
+    assert_eq!(next, at(2026, 10, 13, 9));
+    let next = Recurrence::AfterCompletion(0).next_due(due, at(2026, 10, 10, 18));
+    assert_eq!(next, at(2026, 10, 11, 9));
+}
+
+#[test]
+fn instances_completed_late_are_followed_by_one_not_yet_due() {
+    // Two weeks overdue, completed on Wednesday 2026-10-14
+    let due = at(2026, 9, 30, 9);
+    let completed = at(2026, 10, 14, 20);
+    assert_eq!(Recurrence::Daily.next_due(due, completed), at(2026, 10, 15, 9));
+    let weekly = Recurrence::Weekly(vec![Weekday::Mon, Weekday::Wed]);
+    assert_eq!(weekly.next_due(due, completed), at(2026, 10, 19, 9));
+    assert_eq!(Recurrence::Weekly(Vec::new()).next_due(due, completed), at(2026, 10, 21, 9));
+    assert_eq!(Recurrence::MonthlyOnDay(30).next_due(due, completed), at(2026, 10, 30, 9));
+    // Completed early, the next one is still a day later
+    assert_eq!(Recurrence::Daily.next_due(due, at(2026, 9, 20, 9)), at(2026, 10, 1, 9));
+}
+
+#[test]
+fn recurrences_read_back_what_they_display() {
+    for recurrence in [
+        Recurrence::Daily,
+        Recurrence::weekdays(),
+        Recurrence::Weekly(vec![Weekday::Tue, Weekday::Sat]),
+        Recurrence::Weekly(Vec::new()),
+        Recurrence::MonthlyOnDay(31),
+        Recurrence::AfterCompletion(1),
+        Recurrence::AfterCompletion(14),
+    ] {
+        assert_eq!(recurrence.to_string().parse::<Recurrence>(), Ok(recurrence));
+    }
+    assert_eq!(Recurrence::Weekly(Vec::new()).to_string(), "Weekly");
+    assert!("Weekly on ".parse::<Recurrence>().is_err());
+}
+
+#[test]
+fn completing_a_recurring_todo_adds_the_next_instance() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Water plants");
+    let due = Local::now() + Duration::hours(1);
+    state.update(Message::SetDueDate(id, due));
+    state.update(Message::RecurrenceChanged(id, Some(Recurrence::Daily)));
+    state.update(Message::ToggleTodo(id));
+
+    let done = &state.document().todos[&id];
+    assert!(done.completed);
+    assert!(done.recurrence.is_none());
+    let next = state.document().todos.values().find(|todo| todo.id != id).unwrap();
+    assert!(!next.completed);
+    assert_eq!(next.title, "Water plants");
+    assert_eq!(next.recurrence, Some(Recurrence::Daily));
+    assert_eq!(next.series, done.series);
+    assert_eq!(next.due.unwrap().date_naive(), due.date_naive() + Duration::days(1));
+}
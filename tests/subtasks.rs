@@ -0,0 +1,41 @@
+mod common;
+
+use todo_gui::todo::{Message, TodoId, TodoState};
+use common::add_todo;
+
+fn add_subtask(state: &mut TodoState, id: TodoId, title: &str) {
+    state.update(Message::SubtaskInputChanged(id, title.to_string()));
+    state.update(Message::AddSubtask(id));
+}
+
+#[test]
+fn subtasks_complete_and_reopen_their_todo() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Pack");
+    add_subtask(&mut state, id, "Passport");
+    add_subtask(&mut state, id, "Charger");
+    add_subtask(&mut state, id, "  ");
+    let todo = &state.document().todos[&id];
+    assert_eq!(todo.subtask_progress(), (0, 2));
+    let (first, second) = (todo.subtasks[0].id, todo.subtasks[1].id);
+
+    state.update(Message::ToggleSubtask(id, first));
+    assert!(!state.document().todos[&id].completed);
+    state.update(Message::ToggleSubtask(id, second));
+    assert!(state.document().todos[&id].completed);
+    state.update(Message::ToggleSubtask(id, first));
+    assert!(!state.document().todos[&id].completed);
+    assert_eq!(state.document().todos[&id].subtask_progress(), (1, 2));
+
+    // Completing the todo ticks off what is left
+    state.update(Message::ToggleTodo(id));
+    assert_eq!(state.document().todos[&id].subtask_progress(), (2, 2));
+
+    state.update(Message::DeleteSubtask(id, first));
+    assert_eq!(state.document().todos[&id].subtask_progress(), (1, 1));
+    add_subtask(&mut state, id, "Snacks");
+    let todo = &state.document().todos[&id];
+    assert!(!todo.completed);
+    // Ids are not reused after a deletion
+    assert!(todo.subtasks.iter().all(|subtask| subtask.id != first));
+}
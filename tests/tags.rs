@@ -0,0 +1,45 @@
+mod common;
+
+use todo_gui::todo::{parse_tags, Message, TagMatch, TodoState};
+use common::add_todo;
+
+fn shown(state: &TodoState) -> Vec<&str> {
+    let mut titles: Vec<&str> = state.filtered_todos().iter().map(|todo| todo.title.as_str()).collect();
+    titles.sort();
+    titles
+}
+
+#[test]
+fn tags_are_split_out_of_the_title() {
+    let (title, tags) = parse_tags("Call #Work mom #home #work c# #");
+    assert_eq!(title, "Call mom c# #");
+    assert_eq!(tags.into_iter().collect::<Vec<_>>(), ["home", "work"]);
+    assert_eq!(parse_tags("a##b").0, "a##b");
+}
+
+#[test]
+fn filters_by_any_or_all_selected_tags() {
+    let mut state = TodoState::default();
+    for (title, tags) in [("Report", "#work #urgent"), ("Groceries", "#home"), ("Taxes", "#home #urgent")] {
+        let id = add_todo(&mut state, title);
+        state.update(Message::EditingTodo(id, format!("{} {}", title, tags)));
+        state.update(Message::FinishEditing);
+    }
+    add_todo(&mut state, "Untagged");
+    let counts: Vec<(&str, usize)> = state.all_tags().into_iter().collect();
+    assert_eq!(counts, [("home", 2), ("urgent", 2), ("work", 1)]);
+
+    state.update(Message::ToggleTagFilter(String::from("home")));
+    state.update(Message::ToggleTagFilter(String::from("urgent")));
+    assert_eq!(state.tag_match(), TagMatch::Any);
+    assert_eq!(shown(&state), ["Groceries", "Report", "Taxes"]);
+    state.update(Message::TagMatchChanged(TagMatch::All));
+    assert_eq!(shown(&state), ["Taxes"]);
+
+    let taxes = state.filtered_todos()[0].id;
+    state.update(Message::RemoveTag(taxes, String::from("urgent")));
+    assert!(shown(&state).is_empty());
+
+    state.update(Message::ClearTagFilter);
+    assert_eq!(shown(&state).len(), 4);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use chrono::{Duration, Local};
+use todo_gui::todo::{Message, SearchQuery, SortOrder, Todo, TodoId, TodoState};
+use common::add_todo;
+
+#[test]
+fn parses_search_qualifiers() {
+    let query = SearchQuery::parse("Report updated:7d completed:2w created:12h note:x");
+
+    assert_eq!(query.text, "report note:x");
+    assert_eq!(query.updated_within, Some(Duration::days(7)));
+    assert_eq!(query.completed_within, Some(Duration::weeks(2)));
+    assert_eq!(query.created_within, Some(Duration::hours(12)));
+    assert_eq!(SearchQuery::parse("updated:soon").text, "updated:soon");
+}
+
+#[test]
+fn toggling_and_editing_stamp_the_todo() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Write report");
+    let other = add_todo(&mut state, "Other");
+    let untouched = state.document().todos[&other].updated_at;
+
+    state.update(Message::DescriptionChanged(id, String::from("Draft")));
+    assert!(state.document().todos[&id].updated_at.is_some());
+    assert_eq!(state.document().todos[&other].updated_at, untouched);
+
+    state.update(Message::ToggleTodo(id));
+    let todo = &state.document().todos[&id];
+    assert!(todo.completed_at.is_some());
+
+    state.update(Message::ToggleTodo(id));
+    assert!(state.document().todos[&id].completed_at.is_none());
+}
+
+#[test]
+fn filters_and_sorts_by_completion() {
+    let mut state = TodoState::default();
+    let first = add_todo(&mut state, "First");
+    let second = add_todo(&mut state, "Second");
+    add_todo(&mut state, "Open");
+    state.update(Message::ToggleTodo(second));
+    state.update(Message::ToggleTodo(first));
+
+    state.update(Message::SearchQueryChanged(String::from("completed:1d")));
+    let ids: Vec<TodoId> = state.filtered_todos().iter().map(|todo| todo.id).collect();
+    assert_eq!(ids.len(), 2);
+
+    state.update(Message::SearchQueryChanged(String::new()));
+    state.update(Message::SortChanged(SortOrder::RecentlyCompleted));
+    let titles: Vec<&str> = state.filtered_todos().iter().map(|todo| todo.title.as_str()).collect();
+    assert_eq!(titles, ["First", "Second", "Open"]);
+
+    let todo = state.document().todos[&first].clone();
+    let query = SearchQuery::parse("completed:1h");
+    assert!(!query.matches(&todo, Local::now() + Duration::hours(2)));
+    let reopened = Todo { completed: false, ..todo };
+    assert!(!query.matches(&reopened, Local::now()));
+
+    // A new subtask reopens the todo
+    state.update(Message::SubtaskInputChanged(first, String::from("One more thing")));
+    state.update(Message::AddSubtask(first));
+    assert!(!state.document().todos[&first].completed);
+    assert!(state.document().todos[&first].completed_at.is_none());
+    state.update(Message::SearchQueryChanged(String::from("completed:1d")));
+    assert_eq!(state.filtered_todos().len(), 1);
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use chrono::{Local, NaiveDate};
+use std::fs;
+use todo_gui::storage::{todotxt::meta_path, Storage, TodoTxtStorage};
+use todo_gui::todo::{Message, Priority, TodoId, TodoState};
+use todo_gui::todotxt::{self, Task};
+use common::{add_todo, temp_dir};
+
+fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+#[test]
+fn lines_are_split_into_their_parts() {
+    let task = Task::parse(
+        "(A) 2026-10-01 Call mom +Family_Stuff @phone @Home due:2026-10-20 +Other see:notes",
+    );
+    assert_eq!(task.priority, Some('A'));
+    assert_eq!(task.creation_date, date(2026, 10, 1));
+    assert_eq!(task.title, "Call mom +Other see:notes");
+    assert_eq!(task.project.as_deref(), Some("Family_Stuff"));
+    assert_eq!(task.contexts, vec!["phone", "home"]);
+    assert_eq!(task.due, date(2026, 10, 20));
+    assert!(!task.completed);
+
+    let done = Task::parse("x 2026-10-18 2026-10-01 Pay rent due:soon pri:B");
+    assert!(done.completed);
+    assert_eq!(done.completion_date, date(2026, 10, 18));
+    assert_eq!(done.creation_date, date(2026, 10, 1));
+    assert_eq!(done.priority, Some('B'));
+    assert_eq!(done.title, "Pay rent due:soon");
+
+    // A single date after `x` is when it was completed
+    let done = Task::parse("x 2026-10-18 Water plants");
+    assert_eq!((done.completion_date, done.creation_date), (date(2026, 10, 18), None));
+    // Lowercase letters and an `x` inside the title are just words
+    assert_eq!(Task::parse("(a) fix xbox").title, "(a) fix xbox");
+}
+
+#[test]
+fn todos_survive_a_round_trip_with_their_other_fields() {
+    let mut state = TodoState::default();
+    state.update(Message::NewListNameChanged(String::from("Side project")));
+    state.update(Message::CreateList);
+    let site = add_todo(&mut state, "Build site");
+    state.update(Message::EditingTodo(site, String::from("Build site #web")));
+    state.update(Message::FinishEditing);
+    state.update(Message::PriorityChanged(site, Priority::High));
+    state.update(Message::DescriptionChanged(site, String::from("Static, no CMS")));
+    let milk = add_todo(&mut state, "Milk");
+    state.update(Message::PriorityChanged(milk, Priority::Low));
+    state.update(Message::ToggleTodo(milk));
+    let old = add_todo(&mut state, "Old idea");
+    state.update(Message::DeleteTodo(old));
+    let document = state.document().clone();
+
+    let data = todotxt::write(&document);
+    assert_eq!(data.lines().count(), 2);
+    assert!(data.contains("(B) "));
+    assert!(data.contains("Build site +Side_project @web"));
+    assert!(data.contains("pri:D"));
+    assert!(data.lines().any(|line| line.starts_with("x ")));
+
+    let read = todotxt::read(&data, document.clone(), Local::now());
+    assert_eq!(read, document);
+
+    // Another tool edits the file
+    let edited = data
+        .lines()
+        .filter(|line| !line.contains("Milk"))
+        .map(|line| line.replace("(B) ", "(A) "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n2026-10-18 Buy stamps +Errands @post\n";
+    let read = todotxt::read(&edited, document.clone(), Local::now());
+    assert_eq!(read.todos.len(), 3);
+    assert!(!read.todos.contains_key(&milk));
+    assert_eq!(read.todos[&site].priority, Priority::Urgent);
+    assert_eq!(read.todos[&site].description, "Static, no CMS");
+    assert!(read.todos[&old].is_trashed());
+    let stamps = read.todos.values().find(|todo| todo.title == "Buy stamps").unwrap();
+    assert_eq!(read.lists[&stamps.list_id].name, "Errands");
+    assert!(stamps.tags.contains("post"));
+
+    // Until the ids are written, reading again gives the same todos, also
+    // after an update of the app
+    assert_eq!(todotxt::read(&edited, document, Local::now()), read);
+    assert_eq!(stamps.id.to_string(), "d293f483-59f5-5c94-af75-80d6616a289d");
+    assert_eq!(stamps.id, TodoId::from_key("2026-10-18 Buy stamps +Errands @post\n1"));
+}
+
+#[test]
+fn titles_that_look_like_metadata_survive_a_round_trip() {
+    let mut state = TodoState::default();
+    let titles = [
+        "Reply +1 to Bob",
+        "Lunch @noon at the usual place",
+        "Ask whether due:2026-11-01 still holds",
+        "Rate it pri:A",
+        "Keep \\+literal backslashes \\ and see:notes",
+    ];
+    for title in titles {
+        add_todo(&mut state, title);
+    }
+    let document = state.document().clone();
+
+    let data = todotxt::write(&document);
+    assert!(data.contains("Reply \\+1 to Bob"));
+    let read = todotxt::read(&data, document.clone(), Local::now());
+    assert_eq!(read, document);
+    assert_eq!(read.lists, TodoState::default().document().lists);
+
+    // Also when read as new todos, e.g. by another install
+    let read = todotxt::read(&data, Default::default(), Local::now());
+    let mut read_titles: Vec<&str> = read.todos.values().map(|todo| todo.title.as_str()).collect();
+    read_titles.sort();
+    let mut expected = titles.to_vec();
+    expected.sort();
+    assert_eq!(read_titles, expected);
+    assert!(read.todos.values().all(|todo| todo.tags.is_empty() && todo.due.is_none()));
+}
+
+#[test]
+fn todo_txt_file_is_used_as_storage() {
+    let path = temp_dir("todotxt").join("todo.txt");
+    let storage = TodoTxtStorage::new(path.clone());
+    assert!(storage.load().unwrap().is_none());
+    assert_eq!(storage.data_path(), Some(path.as_path()));
+
+    // A file written by another tool
+    fs::write(&path, "(C) Plan trip @travel\nx 2026-10-02 Renew passport\n").unwrap();
+    let document = storage.load().unwrap().unwrap();
+    let mut state = TodoState::new(document);
+    let trip = state.document().todos.values().find(|todo| todo.title == "Plan trip").unwrap().id;
+    state.update(Message::DescriptionChanged(trip, String::from("Check the train first")));
+    storage.save(state.document()).unwrap();
+
+    let data = fs::read_to_string(&path).unwrap();
+    let today = Local::now().format("%Y-%m-%d");
+    assert!(data.contains(&format!("(C) {} Plan trip @travel id:{}", today, trip)));
+    assert!(meta_path(&path).exists());
+    let loaded = storage.load().unwrap().unwrap();
+    assert_eq!(loaded.todos[&trip].description, "Check the train first");
+    assert_eq!(&loaded, state.document());
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
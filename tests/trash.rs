@@ -0,0 +1,65 @@
+mod common;
+
+use chrono::{Duration, Local};
+use todo_gui::todo::{Filter, ListSelection, Message, TodoState, INBOX_LIST_ID};
+use common::add_todo;
+
+#[test]
+fn deleted_todos_move_to_the_trash_and_back() {
+    let mut state = TodoState::default();
+    let id = add_todo(&mut state, "Buy milk");
+
+    state.update(Message::DeleteTodo(id));
+    assert!(state.filtered_todos().is_empty());
+    assert_eq!(state.trash_count(), 1);
+
+    state.update(Message::FilterChanged(Filter::Trash));
+    assert_eq!(state.filtered_todos()[0].id, id);
+
+    state.update(Message::RestoreTodo(id));
+    assert_eq!(state.trash_count(), 0);
+    state.update(Message::FilterChanged(Filter::All));
+    assert_eq!(state.filtered_todos()[0].id, id);
+}
+
+#[test]
+fn todos_of_a_deleted_list_are_restored_into_the_inbox() {
+    let mut state = TodoState::default();
+    state.update(Message::NewListNameChanged(String::from("Work")));
+    state.update(Message::CreateList);
+    let ListSelection::List(list_id) = state.selected_list() else {
+        panic!("new list is not selected");
+    };
+    let id = add_todo(&mut state, "Release notes");
+
+    state.update(Message::DeleteList(list_id));
+    assert!(state.document().todos[&id].is_trashed());
+
+    state.update(Message::RestoreTodo(id));
+    assert_eq!(state.document().todos[&id].list_id, INBOX_LIST_ID);
+}
+
+#[test]
+fn purges_todos_past_the_retention_period() {
+    let mut state = TodoState::default();
+    let old = add_todo(&mut state, "Old");
+    let recent = add_todo(&mut state, "Recent");
+    state.update(Message::DeleteTodo(old));
+    state.update(Message::DeleteTodo(recent));
+    state.take_changes();
+
+    state.purge_trash(Local::now() + Duration::days(10));
+    assert_eq!(state.trash_count(), 2);
+
+    state.update(Message::TrashRetentionChanged(7));
+    state.take_changes();
+    state.purge_trash(Local::now() + Duration::days(10));
+    assert_eq!(state.trash_count(), 0);
+    assert_eq!(state.take_changes().todos.len(), 2);
+
+    state.update(Message::TrashRetentionChanged(0));
+    let kept = add_todo(&mut state, "Kept");
+    state.update(Message::DeleteTodo(kept));
+    state.purge_trash(Local::now() + Duration::days(1000));
+    assert_eq!(state.trash_count(), 1);
+}
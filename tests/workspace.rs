@@ -0,0 +1,70 @@
+use std::fs;
+use std::path::PathBuf;
+use todo_gui::config::Config;
+use todo_gui::storage::{self, StorageKind};
+use todo_gui::todo::TodoDocument;
+use todo_gui::Flags;
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn command_line_wins_over_environment_and_config() {
+    assert_eq!(
+        Flags::parse(args(&["--data", "team.json"])).data_path,
+        Some(PathBuf::from("team.json"))
+    );
+    assert_eq!(
+        Flags::parse(args(&["--data=team.db"])).data_path,
+        Some(PathBuf::from("team.db"))
+    );
+    assert_eq!(Flags::parse(args(&["--verbose"])).data_path, None);
+
+    let config = Config {
+        data_path: Some(PathBuf::from("config.json")),
+        ..Config::default()
+    };
+    let arg = Some(PathBuf::from("arg.json"));
+    let env = Some(PathBuf::from("env.json"));
+    assert_eq!(config.resolve_data_path(arg, env.clone()), PathBuf::from("arg.json"));
+    assert_eq!(config.resolve_data_path(None, env), PathBuf::from("env.json"));
+    assert_eq!(config.resolve_data_path(None, Some(PathBuf::new())), PathBuf::from("config.json"));
+}
+
+#[test]
+fn recent_workspaces_are_most_recent_first() {
+    let mut config = Config::default();
+    config.remember_workspace(PathBuf::from("personal.json"));
+    config.remember_workspace(PathBuf::from("team.db"));
+    config.remember_workspace(PathBuf::from("personal.json"));
+    assert_eq!(
+        config.recent_workspaces,
+        vec![PathBuf::from("personal.json"), PathBuf::from("team.db")]
+    );
+
+    for index in 0..20 {
+        config.remember_workspace(PathBuf::from(format!("{}.json", index)));
+    }
+    assert_eq!(config.recent_workspaces.len(), 8);
+    assert_eq!(config.recent_workspaces[0], PathBuf::from("19.json"));
+}
+
+#[test]
+fn backend_follows_the_file_extension() {
+    assert_eq!(StorageKind::for_path(&PathBuf::from("todos.json")), StorageKind::Json);
+    assert_eq!(StorageKind::for_path(&PathBuf::from("team.db")), StorageKind::Sqlite);
+    assert_eq!(StorageKind::for_path(&PathBuf::from("log.jsonl")), StorageKind::Journal);
+    assert_eq!(StorageKind::for_path(&PathBuf::from("todo.txt")), StorageKind::TodoTxt);
+    assert_eq!(StorageKind::for_path(&PathBuf::from("todos")), StorageKind::Json);
+
+    let dir = std::env::temp_dir().join(format!("todo_gui_workspace_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("nested").join("team.db");
+    let storage = storage::open(&path);
+    storage.save(&TodoDocument::default()).unwrap();
+    assert!(path.exists());
+    assert!(storage.load().unwrap().is_some());
+
+    fs::remove_dir_all(dir).unwrap();
+}